@@ -0,0 +1,673 @@
+use crate::chip8::{Instruction, Platform};
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+const MAX_INCLUDE_DEPTH: usize = 16;
+const MAX_SYMBOL_DEPTH: usize = 32;
+const LISTING_BYTES_PER_LINE: usize = 4;
+
+#[derive(Debug, PartialEq)]
+pub struct AsmError {
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}: {}",
+            self.file, self.line, self.column, self.message
+        )
+    }
+}
+
+impl Error for AsmError {}
+
+pub struct Program {
+    pub bytes: Vec<u8>,
+    pub listing: String,
+}
+
+pub fn assemble(source: &str) -> Result<Program, AsmError> {
+    let mut assembler = Assembler::new();
+    assembler.read_source(source, Rc::from("<input>"), Path::new("."), 0)?;
+    assembler.finish()
+}
+
+pub fn assemble_file(path: impl AsRef<Path>) -> Result<Program, AsmError> {
+    let path = path.as_ref();
+    let file: Rc<str> = Rc::from(path.display().to_string());
+    let source = fs::read_to_string(path).map_err(|error| AsmError {
+        file: file.to_string(),
+        line: 0,
+        column: 0,
+        message: error.to_string(),
+    })?;
+    let directory = path.parent().unwrap_or_else(|| Path::new("."));
+    let mut assembler = Assembler::new();
+    assembler.read_source(&source, file, directory, 0)?;
+    assembler.finish()
+}
+
+#[derive(Clone)]
+struct Location {
+    file: Rc<str>,
+    line: usize,
+}
+
+impl Location {
+    fn error(&self, column: usize, message: impl Into<String>) -> AsmError {
+        AsmError {
+            file: self.file.to_string(),
+            line: self.line,
+            column,
+            message: message.into(),
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Token {
+    text: String,
+    column: usize,
+}
+
+enum Symbol {
+    Label(usize),
+    Constant(Location, Token),
+}
+
+struct Statement {
+    location: Location,
+    mnemonic: Token,
+    operands: Vec<Token>,
+}
+
+struct SourceLine {
+    address: usize,
+    text: String,
+    statement: Option<Statement>,
+}
+
+enum Operand {
+    Register(usize),
+    I,
+    IndirectI,
+    Delay,
+    Sound,
+    Key,
+    Font,
+    LargeFont,
+    Decimal,
+    Flags,
+    Long(i64, usize),
+    Value(i64, usize),
+}
+
+struct Assembler {
+    address: usize,
+    symbols: HashMap<String, Symbol>,
+    lines: Vec<SourceLine>,
+}
+
+impl Assembler {
+    fn new() -> Self {
+        Self {
+            address: Platform::Chip8.program_start() as usize,
+            symbols: HashMap::new(),
+            lines: Vec::new(),
+        }
+    }
+
+    fn read_source(
+        &mut self,
+        source: &str,
+        file: Rc<str>,
+        directory: &Path,
+        depth: usize,
+    ) -> Result<(), AsmError> {
+        for (index, text) in source.lines().enumerate() {
+            let location = Location {
+                file: file.clone(),
+                line: index + 1,
+            };
+            self.read_line(text, location, directory, depth)?;
+        }
+        Ok(())
+    }
+
+    fn read_line(
+        &mut self,
+        text: &str,
+        location: Location,
+        directory: &Path,
+        depth: usize,
+    ) -> Result<(), AsmError> {
+        let address = self.address;
+        let code = strip_comment(text);
+        let mut words = code;
+        let mut offset = 0;
+
+        let trimmed = words.trim_start();
+        let name_length = trimmed
+            .find(|c: char| !is_symbol_char(c))
+            .unwrap_or(trimmed.len());
+        if name_length > 0 && trimmed[name_length..].starts_with(':') {
+            let column = code.len() - trimmed.len() + 1;
+            self.define(
+                &trimmed[..name_length],
+                Symbol::Label(address),
+                &location,
+                column,
+            )?;
+            offset = code.len() - trimmed.len() + name_length + 1;
+            words = &code[offset..];
+        }
+
+        let mnemonic = match next_word(words, offset) {
+            Some(token) => token,
+            None => {
+                self.push_line(address, text, None);
+                return Ok(());
+            }
+        };
+        let rest_offset = mnemonic.column - 1 + mnemonic.text.len();
+        let rest = &code[rest_offset..];
+
+        if let Some(equ) = next_word(rest, rest_offset) {
+            if equ.text.eq_ignore_ascii_case("EQU") {
+                let value_offset = equ.column - 1 + equ.text.len();
+                let value = split_operands(&code[value_offset..], value_offset);
+                if value.len() != 1 {
+                    return Err(location.error(equ.column, "EQU expects one value"));
+                }
+                let symbol = Symbol::Constant(location.clone(), value[0].clone());
+                self.define(&mnemonic.text, symbol, &location, mnemonic.column)?;
+                self.push_line(address, text, None);
+                return Ok(());
+            }
+        }
+
+        let operands = split_operands(rest, rest_offset);
+        let size = match mnemonic.text.to_uppercase().as_str() {
+            "INCLUDE" => {
+                self.push_line(address, text, None);
+                return self.include(&operands, &location, &mnemonic, directory, depth);
+            }
+            "DB" => {
+                let mut size = 0;
+                for operand in &operands {
+                    size += match parse_string(&operand.text) {
+                        Some(string) => string.len(),
+                        None => 1,
+                    };
+                }
+                size
+            }
+            "DW" => operands.len() * 2,
+            "LD" if operands.len() == 2
+                && operands[0].text.eq_ignore_ascii_case("I")
+                && is_long(&operands[1].text) =>
+            {
+                4
+            }
+            _ => 2,
+        };
+
+        self.address += size;
+        let statement = Statement {
+            location,
+            mnemonic,
+            operands,
+        };
+        self.push_line(address, text, Some(statement));
+        Ok(())
+    }
+
+    fn include(
+        &mut self,
+        operands: &[Token],
+        location: &Location,
+        mnemonic: &Token,
+        directory: &Path,
+        depth: usize,
+    ) -> Result<(), AsmError> {
+        let (operand, name) = match operands {
+            [operand] => match parse_string(&operand.text) {
+                Some(name) => (operand, name),
+                None => return Err(location.error(operand.column, "expected a quoted file name")),
+            },
+            _ => return Err(location.error(mnemonic.column, "INCLUDE expects one file name")),
+        };
+        if depth >= MAX_INCLUDE_DEPTH {
+            return Err(location.error(operand.column, "includes are nested too deeply"));
+        }
+        let path: PathBuf = directory.join(name);
+        let source = fs::read_to_string(&path).map_err(|error| {
+            location.error(
+                operand.column,
+                format!("failed to read {}: {}", path.display(), error),
+            )
+        })?;
+        let file: Rc<str> = Rc::from(path.display().to_string());
+        let directory = path.parent().unwrap_or(directory).to_path_buf();
+        self.read_source(&source, file, &directory, depth + 1)
+    }
+
+    fn define(
+        &mut self,
+        name: &str,
+        symbol: Symbol,
+        location: &Location,
+        column: usize,
+    ) -> Result<(), AsmError> {
+        if !name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            || !name.chars().all(is_symbol_char)
+        {
+            return Err(location.error(column, format!("invalid symbol name '{}'", name)));
+        }
+        if self.symbols.contains_key(name) {
+            return Err(location.error(column, format!("'{}' is already defined", name)));
+        }
+        self.symbols.insert(name.to_owned(), symbol);
+        Ok(())
+    }
+
+    fn push_line(&mut self, address: usize, text: &str, statement: Option<Statement>) {
+        self.lines.push(SourceLine {
+            address,
+            text: text.to_owned(),
+            statement,
+        });
+    }
+
+    fn finish(self) -> Result<Program, AsmError> {
+        let mut bytes = Vec::new();
+        let mut listing = String::new();
+        for line in &self.lines {
+            let emitted = match &line.statement {
+                Some(statement) => self.emit(statement)?,
+                None => Vec::new(),
+            };
+            listing.push_str(&listing_line(line.address, &emitted, &line.text));
+            bytes.extend_from_slice(&emitted);
+        }
+        Ok(Program { bytes, listing })
+    }
+
+    fn emit(&self, statement: &Statement) -> Result<Vec<u8>, AsmError> {
+        let location = &statement.location;
+        match statement.mnemonic.text.to_uppercase().as_str() {
+            "DB" => {
+                let mut bytes = Vec::new();
+                for operand in &statement.operands {
+                    match parse_string(&operand.text) {
+                        Some(string) => bytes.extend_from_slice(string.as_bytes()),
+                        None => {
+                            let value = self.evaluate(operand, location, 0)?;
+                            bytes.push(check(value, -0x80, 0xFF, location, operand.column)? as u8);
+                        }
+                    }
+                }
+                Ok(bytes)
+            }
+            "DW" => {
+                let mut bytes = Vec::new();
+                for operand in &statement.operands {
+                    let value = self.evaluate(operand, location, 0)?;
+                    let word = check(value, -0x8000, 0xFFFF, location, operand.column)? as u16;
+                    bytes.extend_from_slice(&word.to_be_bytes());
+                }
+                Ok(bytes)
+            }
+            _ => self.emit_instruction(statement),
+        }
+    }
+
+    fn emit_instruction(&self, statement: &Statement) -> Result<Vec<u8>, AsmError> {
+        use Instruction::*;
+        use Operand::*;
+
+        let location = &statement.location;
+        let mnemonic = statement.mnemonic.text.to_uppercase();
+        let mut operands = Vec::new();
+        for token in &statement.operands {
+            operands.push(self.operand(token, location)?);
+        }
+        let address =
+            |value: i64, column| check(value, 0, 0xFFF, location, column).map(|v| v as u16);
+        let byte =
+            |value: i64, column| check(value, -0x80, 0xFF, location, column).map(|v| v as u8);
+        let nibble = |value: i64, column| check(value, 0, 0xF, location, column).map(|v| v as u8);
+
+        let instruction = match (mnemonic.as_str(), operands.as_slice()) {
+            ("CLS", []) => Clear,
+            ("RET", []) => Return,
+            ("SCD", [Value(rows, column)]) => ScrollDown(nibble(*rows, *column)?),
+            ("SCU", [Value(rows, column)]) => ScrollUp(nibble(*rows, *column)?),
+            ("SCR", []) => ScrollRight,
+            ("SCL", []) => ScrollLeft,
+            ("EXIT", []) => Exit,
+            ("LOW", []) => Lores,
+            ("HIGH", []) => Hires,
+            ("JP", [Value(target, column)]) => Jump(address(*target, *column)?),
+            ("JP", [Register(0), Value(target, column)]) => JumpV0(address(*target, *column)?),
+            ("CALL", [Value(target, column)]) => Call(address(*target, *column)?),
+            ("SE", [Register(x), Register(y)]) => SkipEqReg { x: *x, y: *y },
+            ("SE", [Register(x), Value(value, column)]) => SkipEqByte {
+                x: *x,
+                byte: byte(*value, *column)?,
+            },
+            ("SNE", [Register(x), Register(y)]) => SkipNeReg { x: *x, y: *y },
+            ("SNE", [Register(x), Value(value, column)]) => SkipNeByte {
+                x: *x,
+                byte: byte(*value, *column)?,
+            },
+            ("SAVE", [Register(x), Register(y)]) => SaveRange { x: *x, y: *y },
+            ("LOAD", [Register(x), Register(y)]) => LoadRange { x: *x, y: *y },
+            ("LD", [Register(x), Register(y)]) => LoadReg { x: *x, y: *y },
+            ("LD", [Register(x), Value(value, column)]) => LoadByte {
+                x: *x,
+                byte: byte(*value, *column)?,
+            },
+            ("LD", [I, Value(target, column)]) => LoadI(address(*target, *column)?),
+            ("LD", [I, Long(target, column)]) => {
+                let target = check(*target, 0, 0xFFFF, location, *column)? as u16;
+                let mut bytes = LoadLongI.encode().to_be_bytes().to_vec();
+                bytes.extend_from_slice(&target.to_be_bytes());
+                return Ok(bytes);
+            }
+            ("LD", [Register(x), Delay]) => LoadDelay(*x),
+            ("LD", [Register(x), Key]) => WaitKey(*x),
+            ("LD", [Delay, Register(x)]) => SetDelay(*x),
+            ("LD", [Sound, Register(x)]) => SetSound(*x),
+            ("LD", [Font, Register(x)]) => LoadFont(*x),
+            ("LD", [LargeFont, Register(x)]) => LoadLargeFont(*x),
+            ("LD", [Decimal, Register(x)]) => Bcd(*x),
+            ("LD", [IndirectI, Register(x)]) => Store(*x),
+            ("LD", [Register(x), IndirectI]) => Read(*x),
+            ("LD", [Flags, Register(x)]) => SaveFlags(*x),
+            ("LD", [Register(x), Flags]) => LoadFlags(*x),
+            ("ADD", [Register(x), Register(y)]) => AddReg { x: *x, y: *y },
+            ("ADD", [Register(x), Value(value, column)]) => AddByte {
+                x: *x,
+                byte: byte(*value, *column)?,
+            },
+            ("ADD", [I, Register(x)]) => AddI(*x),
+            ("OR", [Register(x), Register(y)]) => Or { x: *x, y: *y },
+            ("AND", [Register(x), Register(y)]) => And { x: *x, y: *y },
+            ("XOR", [Register(x), Register(y)]) => Xor { x: *x, y: *y },
+            ("SUB", [Register(x), Register(y)]) => Sub { x: *x, y: *y },
+            ("SUBN", [Register(x), Register(y)]) => SubN { x: *x, y: *y },
+            ("SHR", [Register(x)]) => ShiftRight { x: *x, y: *x },
+            ("SHR", [Register(x), Register(y)]) => ShiftRight { x: *x, y: *y },
+            ("SHL", [Register(x)]) => ShiftLeft { x: *x, y: *x },
+            ("SHL", [Register(x), Register(y)]) => ShiftLeft { x: *x, y: *y },
+            ("RND", [Register(x), Value(value, column)]) => Random {
+                x: *x,
+                byte: byte(*value, *column)?,
+            },
+            ("DRW", [Register(x), Register(y), Value(rows, column)]) => Draw {
+                x: *x,
+                y: *y,
+                rows: nibble(*rows, *column)?,
+            },
+            ("SKP", [Register(x)]) => SkipKey(*x),
+            ("SKNP", [Register(x)]) => SkipNotKey(*x),
+            ("PLANE", [Value(planes, column)]) => SelectPlanes(nibble(*planes, *column)?),
+            ("AUDIO", []) => LoadAudio,
+            ("PITCH", [Register(x)]) => LoadPitch(*x),
+            _ if is_mnemonic(&mnemonic) => {
+                return Err(location.error(
+                    statement.mnemonic.column,
+                    format!("invalid operands for {}", mnemonic),
+                ))
+            }
+            _ => {
+                return Err(location.error(
+                    statement.mnemonic.column,
+                    format!("unknown mnemonic '{}'", statement.mnemonic.text),
+                ))
+            }
+        };
+
+        Ok(instruction.encode().to_be_bytes().to_vec())
+    }
+
+    fn operand(&self, token: &Token, location: &Location) -> Result<Operand, AsmError> {
+        let upper = token.text.to_uppercase();
+        let operand = match upper.as_str() {
+            "I" => Operand::I,
+            "[I]" => Operand::IndirectI,
+            "DT" => Operand::Delay,
+            "ST" => Operand::Sound,
+            "K" => Operand::Key,
+            "F" => Operand::Font,
+            "HF" => Operand::LargeFont,
+            "B" => Operand::Decimal,
+            "R" => Operand::Flags,
+            _ if upper.len() == 2 && upper.starts_with('V') => {
+                match usize::from_str_radix(&upper[1..], 16) {
+                    Ok(register) => Operand::Register(register),
+                    Err(_) => Operand::Value(self.evaluate(token, location, 0)?, token.column),
+                }
+            }
+            _ if is_long(&token.text) => {
+                let expression = Token {
+                    text: token.text[4..].trim_start().to_owned(),
+                    column: token.column + token.text.len() - token.text[4..].trim_start().len(),
+                };
+                Operand::Long(self.evaluate(&expression, location, 0)?, expression.column)
+            }
+            _ => Operand::Value(self.evaluate(token, location, 0)?, token.column),
+        };
+        Ok(operand)
+    }
+
+    fn evaluate(&self, token: &Token, location: &Location, depth: usize) -> Result<i64, AsmError> {
+        if depth > MAX_SYMBOL_DEPTH {
+            return Err(location.error(token.column, "constant refers to itself"));
+        }
+        let text = &token.text;
+        let mut total = 0;
+        let mut sign = 1;
+        let mut expect_term = true;
+        let mut index = 0;
+        while index < text.len() {
+            let rest = &text[index..];
+            let c = rest.chars().next().unwrap();
+            let column = token.column + index;
+            if c.is_whitespace() {
+                index += c.len_utf8();
+            } else if c == '+' || c == '-' {
+                if !expect_term {
+                    expect_term = true;
+                    sign = 1;
+                }
+                if c == '-' {
+                    sign = -sign;
+                }
+                index += 1;
+            } else if is_symbol_char(c) && expect_term {
+                let length = rest
+                    .find(|c: char| !is_symbol_char(c))
+                    .unwrap_or(rest.len());
+                let term = &rest[..length];
+                total += sign * self.term(term, location, column, depth)?;
+                expect_term = false;
+                index += length;
+            } else {
+                return Err(location.error(column, format!("unexpected '{}'", c)));
+            }
+        }
+        if expect_term {
+            let column = token.column + text.len();
+            return Err(location.error(column, "expected a value"));
+        }
+        Ok(total)
+    }
+
+    fn term(
+        &self,
+        term: &str,
+        location: &Location,
+        column: usize,
+        depth: usize,
+    ) -> Result<i64, AsmError> {
+        if term.starts_with(|c: char| c.is_ascii_digit()) {
+            let lower = term.to_lowercase();
+            let parsed = if let Some(hex) = lower.strip_prefix("0x") {
+                i64::from_str_radix(hex, 16)
+            } else if let Some(binary) = lower.strip_prefix("0b") {
+                i64::from_str_radix(binary, 2)
+            } else {
+                lower.parse()
+            };
+            return parsed
+                .map_err(|_| location.error(column, format!("invalid number '{}'", term)));
+        }
+        match self.symbols.get(term) {
+            Some(Symbol::Label(address)) => Ok(*address as i64),
+            Some(Symbol::Constant(defined, value)) => self.evaluate(value, defined, depth + 1),
+            None => Err(location.error(column, format!("undefined symbol '{}'", term))),
+        }
+    }
+}
+
+fn check(
+    value: i64,
+    min: i64,
+    max: i64,
+    location: &Location,
+    column: usize,
+) -> Result<i64, AsmError> {
+    if value < min || value > max {
+        return Err(location.error(
+            column,
+            format!("value {} is out of range ({} to {})", value, min, max),
+        ));
+    }
+    Ok(value)
+}
+
+fn is_mnemonic(mnemonic: &str) -> bool {
+    [
+        "CLS", "RET", "SCD", "SCU", "SCR", "SCL", "EXIT", "LOW", "HIGH", "JP", "CALL", "SE", "SNE",
+        "SAVE", "LOAD", "LD", "ADD", "OR", "AND", "XOR", "SUB", "SUBN", "SHR", "SHL", "RND", "DRW",
+        "SKP", "SKNP", "PLANE", "AUDIO", "PITCH",
+    ]
+    .contains(&mnemonic)
+}
+
+fn is_symbol_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '.'
+}
+
+fn is_long(text: &str) -> bool {
+    match (text.get(..4), text.get(4..)) {
+        (Some(prefix), Some(rest)) => {
+            prefix.eq_ignore_ascii_case("LONG") && rest.starts_with(char::is_whitespace)
+        }
+        _ => false,
+    }
+}
+
+fn strip_comment(text: &str) -> &str {
+    let mut quoted = false;
+    for (index, c) in text.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ';' if !quoted => return &text[..index],
+            _ => {}
+        }
+    }
+    text
+}
+
+fn next_word(text: &str, offset: usize) -> Option<Token> {
+    let trimmed = text.trim_start();
+    if trimmed.is_empty() {
+        return None;
+    }
+    let length = trimmed.find(char::is_whitespace).unwrap_or(trimmed.len());
+    Some(Token {
+        text: trimmed[..length].to_owned(),
+        column: offset + text.len() - trimmed.len() + 1,
+    })
+}
+
+fn split_operands(text: &str, offset: usize) -> Vec<Token> {
+    let mut operands = Vec::new();
+    if text.trim().is_empty() {
+        return operands;
+    }
+    let mut start = 0;
+    let mut quoted = false;
+    for (index, c) in text
+        .char_indices()
+        .chain(std::iter::once((text.len(), ',')))
+    {
+        match c {
+            '"' => quoted = !quoted,
+            ',' if !quoted => {
+                let part = &text[start..index];
+                let trimmed = part.trim_start();
+                operands.push(Token {
+                    text: trimmed.trim_end().to_owned(),
+                    column: offset + start + part.len() - trimmed.len() + 1,
+                });
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    operands
+}
+
+fn parse_string(text: &str) -> Option<&str> {
+    if text.len() >= 2 && text.starts_with('"') && text.ends_with('"') {
+        Some(&text[1..text.len() - 1])
+    } else {
+        None
+    }
+}
+
+fn listing_line(address: usize, bytes: &[u8], text: &str) -> String {
+    let hex = |chunk: &[u8]| {
+        chunk
+            .chunks(2)
+            .map(|word| {
+                word.iter()
+                    .map(|byte| format!("{:02X}", byte))
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    let mut chunks = bytes.chunks(LISTING_BYTES_PER_LINE);
+    let mut line = format!(
+        "{:03X}  {:<9}  {}\n",
+        address,
+        chunks.next().map(hex).unwrap_or_default(),
+        text
+    );
+    for (index, chunk) in chunks.enumerate() {
+        line.push_str(&format!(
+            "{:03X}  {}\n",
+            address + (index + 1) * LISTING_BYTES_PER_LINE,
+            hex(chunk)
+        ));
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests;
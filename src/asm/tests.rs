@@ -0,0 +1,120 @@
+use super::{assemble, assemble_file, AsmError};
+use crate::chip8::{Instruction, Platform};
+
+use std::env;
+use std::fs;
+
+#[test]
+fn test_assemble() {
+    let source = "\
+SPEED equ 2 ; pixels per frame
+start:
+    LD V0, 0
+    LD I, sprite
+loop: DRW V0, V1, 3
+    ADD V0, SPEED
+    SE V0, 64 - SPEED
+    JP loop
+    JP start
+sprite:
+    db 0b10100000, 0x40, 0xA0
+    dw 0x1234
+";
+
+    let program = assemble(source).unwrap();
+
+    assert_eq!(
+        program.bytes,
+        vec![
+            0x60, 0x00, 0xA2, 0x0E, 0xD0, 0x13, 0x70, 0x02, 0x30, 0x3E, 0x12, 0x04, 0x12, 0x00,
+            0xA0, 0x40, 0xA0, 0x12, 0x34,
+        ]
+    );
+    let listing: Vec<&str> = program.listing.lines().collect();
+    assert_eq!(listing[0], "200             SPEED equ 2 ; pixels per frame");
+    assert_eq!(listing[4], "204  D013       loop: DRW V0, V1, 3");
+    assert_eq!(listing[10], "20E  A040 A0        db 0b10100000, 0x40, 0xA0");
+}
+
+#[test]
+fn test_assemble_mnemonics() {
+    for opcode in 0..=0xFFFF {
+        let instruction = match Instruction::decode(opcode, Platform::XoChip) {
+            Ok(Instruction::LoadLongI) | Err(_) => continue,
+            Ok(instruction) => instruction,
+        };
+        let program = assemble(&instruction.to_string()).unwrap();
+        assert_eq!(
+            program.bytes,
+            instruction.encode().to_be_bytes(),
+            "{}",
+            instruction
+        );
+    }
+
+    let program = assemble("ld i, long 0x1234\nshr va\nadd v1, -1").unwrap();
+    assert_eq!(
+        program.bytes,
+        vec![0xF0, 0x00, 0x12, 0x34, 0x8A, 0xA6, 0x71, 0xFF]
+    );
+}
+
+#[test]
+fn test_assemble_errors() {
+    let error = |source| assemble(source).err().unwrap();
+
+    assert_eq!(
+        error("  CLS\n  MOV V0, 1"),
+        AsmError {
+            file: "<input>".to_owned(),
+            line: 2,
+            column: 3,
+            message: "unknown mnemonic 'MOV'".to_owned(),
+        }
+    );
+    assert_eq!(error("LD V0, 0x100").column, 8);
+    assert_eq!(
+        error("LD V0, 0x100").message,
+        "value 256 is out of range (-128 to 255)"
+    );
+    assert_eq!(error("JP missing").message, "undefined symbol 'missing'");
+    assert_eq!(error("JP V1").message, "invalid operands for JP");
+    assert_eq!(error("a:\na: CLS").line, 2);
+    assert_eq!(error("LD V0, 1 +").column, 11);
+    assert_eq!(
+        error("A equ B\nB equ A\nJP A").message,
+        "constant refers to itself"
+    );
+    assert_eq!(error("LD I, abcé").message, "undefined symbol 'abc'");
+    assert_eq!(error("abc: LD I, abcé").message, "unexpected 'é'");
+    assert_eq!(error("LD I, é").column, 7);
+    assert_eq!(error("LD V0, 1\u{a0}+ é").message, "unexpected 'é'");
+    assert_eq!(
+        assemble("LD V0, 1\u{a0}+ 2").unwrap().bytes,
+        vec![0x60, 0x03]
+    );
+}
+
+#[test]
+fn test_assemble_include() {
+    let directory = env::temp_dir().join(format!("yaci-asm-{}", std::process::id()));
+    fs::create_dir_all(&directory).unwrap();
+    fs::write(
+        directory.join("main.asm"),
+        "include \"lib.asm\"\nCALL clear\n",
+    )
+    .unwrap();
+    fs::write(directory.join("lib.asm"), "clear: CLS\n  RET\n  bogus\n").unwrap();
+
+    let error = assemble_file(directory.join("main.asm")).err().unwrap();
+
+    assert!(error.file.ends_with("lib.asm"));
+    assert_eq!((error.line, error.column), (3, 3));
+
+    fs::write(directory.join("lib.asm"), "clear: CLS\n  RET\n").unwrap();
+
+    let program = assemble_file(directory.join("main.asm")).unwrap();
+
+    assert_eq!(program.bytes, vec![0x00, 0xE0, 0x00, 0xEE, 0x22, 0x00]);
+    fs::remove_dir_all(&directory).unwrap();
+}
@@ -0,0 +1,78 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+#[derive(Debug)]
+pub enum VmError {
+    UnknownOpcode { opcode: u16, pc: u16 },
+    StackOverflow { pc: u16, stack: Vec<u16> },
+    StackUnderflow { pc: u16, stack: Vec<u16> },
+    MemoryOutOfBounds { address: usize, pc: u16 },
+    PcOutOfBounds { pc: u16 },
+    RomTooLarge { size: usize, max: usize },
+    InvalidState(String),
+    Io(io::Error),
+}
+
+impl fmt::Display for VmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VmError::UnknownOpcode { opcode, pc } => {
+                write!(f, "unknown opcode {:04X} at {:03X}", opcode, pc)
+            }
+            VmError::StackOverflow { pc, stack } => write!(
+                f,
+                "stack overflow at {:03X} (call stack: {})",
+                pc,
+                format_stack(stack)
+            ),
+            VmError::StackUnderflow { pc, stack } => write!(
+                f,
+                "stack underflow at {:03X} (call stack: {})",
+                pc,
+                format_stack(stack)
+            ),
+            VmError::MemoryOutOfBounds { address, pc } => write!(
+                f,
+                "memory access out of bounds at {:04X} (pc {:03X})",
+                address, pc
+            ),
+            VmError::PcOutOfBounds { pc } => {
+                write!(f, "program counter ran off the end of memory at {:04X}", pc)
+            }
+            VmError::RomTooLarge { size, max } => write!(
+                f,
+                "rom is {} bytes but only {} bytes fit in memory",
+                size, max
+            ),
+            VmError::InvalidState(reason) => write!(f, "invalid save state: {}", reason),
+            VmError::Io(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+fn format_stack(stack: &[u16]) -> String {
+    if stack.is_empty() {
+        return "empty".to_owned();
+    }
+    stack
+        .iter()
+        .map(|address| format!("{:03X}", address))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+impl Error for VmError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            VmError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for VmError {
+    fn from(error: io::Error) -> Self {
+        VmError::Io(error)
+    }
+}
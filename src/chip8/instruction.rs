@@ -0,0 +1,340 @@
+use super::{Platform, HIRES_CHIP8_CLEAR};
+use crate::bitwise::*;
+
+use std::error::Error;
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DecodeError {
+    pub opcode: u16,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unknown opcode {:04X}", self.opcode)
+    }
+}
+
+impl Error for DecodeError {}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Instruction {
+    Clear,
+    Return,
+    ScrollDown(u8),
+    ScrollUp(u8),
+    ScrollRight,
+    ScrollLeft,
+    Exit,
+    Lores,
+    Hires,
+    Jump(u16),
+    Call(u16),
+    SkipEqByte { x: usize, byte: u8 },
+    SkipNeByte { x: usize, byte: u8 },
+    SkipEqReg { x: usize, y: usize },
+    SaveRange { x: usize, y: usize },
+    LoadRange { x: usize, y: usize },
+    LoadByte { x: usize, byte: u8 },
+    AddByte { x: usize, byte: u8 },
+    LoadReg { x: usize, y: usize },
+    Or { x: usize, y: usize },
+    And { x: usize, y: usize },
+    Xor { x: usize, y: usize },
+    AddReg { x: usize, y: usize },
+    Sub { x: usize, y: usize },
+    ShiftRight { x: usize, y: usize },
+    SubN { x: usize, y: usize },
+    ShiftLeft { x: usize, y: usize },
+    SkipNeReg { x: usize, y: usize },
+    LoadI(u16),
+    JumpV0(u16),
+    Random { x: usize, byte: u8 },
+    Draw { x: usize, y: usize, rows: u8 },
+    SkipKey(usize),
+    SkipNotKey(usize),
+    LoadLongI,
+    SelectPlanes(u8),
+    LoadAudio,
+    LoadPitch(usize),
+    LoadDelay(usize),
+    WaitKey(usize),
+    SetDelay(usize),
+    SetSound(usize),
+    AddI(usize),
+    LoadFont(usize),
+    LoadLargeFont(usize),
+    Bcd(usize),
+    Store(usize),
+    Read(usize),
+    SaveFlags(usize),
+    LoadFlags(usize),
+}
+
+impl Instruction {
+    pub fn decode(opcode: u16, platform: Platform) -> Result<Self, DecodeError> {
+        use Instruction::*;
+
+        let end = opcode & 0xFFF;
+        let (x, y, byte) = (get_x(opcode), get_y(opcode), get_byte(opcode));
+        let nibble = get_nibble(opcode) as u8;
+        let schip = platform.supports_schip();
+        let xochip = platform.supports_xochip();
+        let hires_chip8 = platform.supports_hires_chip8();
+        let instruction = match (opcode & 0xF000) >> 12 {
+            0x0 => match end {
+                0x0E0 => Clear,
+                0x0EE => Return,
+                HIRES_CHIP8_CLEAR if hires_chip8 => Clear,
+                0x0C0..=0x0CF if schip => ScrollDown(nibble),
+                0x0D0..=0x0DF if xochip => ScrollUp(nibble),
+                0x0FB if schip => ScrollRight,
+                0x0FC if schip => ScrollLeft,
+                0x0FD if schip => Exit,
+                0x0FE if schip => Lores,
+                0x0FF if schip => Hires,
+                _ => return Err(DecodeError { opcode }),
+            },
+            0x1 => Jump(end),
+            0x2 => Call(end),
+            0x3 => SkipEqByte { x, byte },
+            0x4 => SkipNeByte { x, byte },
+            0x5 => match nibble {
+                0x2 if xochip => SaveRange { x, y },
+                0x3 if xochip => LoadRange { x, y },
+                _ => SkipEqReg { x, y },
+            },
+            0x6 => LoadByte { x, byte },
+            0x7 => AddByte { x, byte },
+            0x8 => match nibble {
+                0x0 => LoadReg { x, y },
+                0x1 => Or { x, y },
+                0x2 => And { x, y },
+                0x3 => Xor { x, y },
+                0x4 => AddReg { x, y },
+                0x5 => Sub { x, y },
+                0x6 => ShiftRight { x, y },
+                0x7 => SubN { x, y },
+                0xE => ShiftLeft { x, y },
+                _ => return Err(DecodeError { opcode }),
+            },
+            0x9 => SkipNeReg { x, y },
+            0xA => LoadI(end),
+            0xB => JumpV0(end),
+            0xC => Random { x, byte },
+            0xD => Draw { x, y, rows: nibble },
+            0xE => match byte {
+                0x9E => SkipKey(x),
+                0xA1 => SkipNotKey(x),
+                _ => return Err(DecodeError { opcode }),
+            },
+            0xF => match byte {
+                0x00 if xochip && end == 0 => LoadLongI,
+                0x01 if xochip => SelectPlanes(x as u8),
+                0x02 if xochip && end == 0x002 => LoadAudio,
+                0x3A if xochip => LoadPitch(x),
+                0x07 => LoadDelay(x),
+                0x0A => WaitKey(x),
+                0x15 => SetDelay(x),
+                0x18 => SetSound(x),
+                0x1E => AddI(x),
+                0x29 => LoadFont(x),
+                0x30 if schip => LoadLargeFont(x),
+                0x33 => Bcd(x),
+                0x55 => Store(x),
+                0x65 => Read(x),
+                0x75 if schip => SaveFlags(x),
+                0x85 if schip => LoadFlags(x),
+                _ => return Err(DecodeError { opcode }),
+            },
+            _ => return Err(DecodeError { opcode }),
+        };
+
+        Ok(instruction)
+    }
+
+    pub fn encode(self) -> u16 {
+        use Instruction::*;
+
+        let xy = |x: usize, y: usize| (x as u16 & 0xF) << 8 | (y as u16 & 0xF) << 4;
+        let xb = |x: usize, byte: u8| (x as u16 & 0xF) << 8 | byte as u16;
+        match self {
+            Clear => 0x00E0,
+            Return => 0x00EE,
+            ScrollDown(rows) => 0x00C0 | (rows as u16 & 0xF),
+            ScrollUp(rows) => 0x00D0 | (rows as u16 & 0xF),
+            ScrollRight => 0x00FB,
+            ScrollLeft => 0x00FC,
+            Exit => 0x00FD,
+            Lores => 0x00FE,
+            Hires => 0x00FF,
+            Jump(address) => 0x1000 | (address & 0xFFF),
+            Call(address) => 0x2000 | (address & 0xFFF),
+            SkipEqByte { x, byte } => 0x3000 | xb(x, byte),
+            SkipNeByte { x, byte } => 0x4000 | xb(x, byte),
+            SkipEqReg { x, y } => 0x5000 | xy(x, y),
+            SaveRange { x, y } => 0x5002 | xy(x, y),
+            LoadRange { x, y } => 0x5003 | xy(x, y),
+            LoadByte { x, byte } => 0x6000 | xb(x, byte),
+            AddByte { x, byte } => 0x7000 | xb(x, byte),
+            LoadReg { x, y } => 0x8000 | xy(x, y),
+            Or { x, y } => 0x8001 | xy(x, y),
+            And { x, y } => 0x8002 | xy(x, y),
+            Xor { x, y } => 0x8003 | xy(x, y),
+            AddReg { x, y } => 0x8004 | xy(x, y),
+            Sub { x, y } => 0x8005 | xy(x, y),
+            ShiftRight { x, y } => 0x8006 | xy(x, y),
+            SubN { x, y } => 0x8007 | xy(x, y),
+            ShiftLeft { x, y } => 0x800E | xy(x, y),
+            SkipNeReg { x, y } => 0x9000 | xy(x, y),
+            LoadI(address) => 0xA000 | (address & 0xFFF),
+            JumpV0(address) => 0xB000 | (address & 0xFFF),
+            Random { x, byte } => 0xC000 | xb(x, byte),
+            Draw { x, y, rows } => 0xD000 | xy(x, y) | (rows as u16 & 0xF),
+            SkipKey(x) => 0xE09E | xy(x, 0),
+            SkipNotKey(x) => 0xE0A1 | xy(x, 0),
+            LoadLongI => 0xF000,
+            SelectPlanes(planes) => 0xF001 | xy(planes as usize, 0),
+            LoadAudio => 0xF002,
+            LoadPitch(x) => 0xF03A | xy(x, 0),
+            LoadDelay(x) => 0xF007 | xy(x, 0),
+            WaitKey(x) => 0xF00A | xy(x, 0),
+            SetDelay(x) => 0xF015 | xy(x, 0),
+            SetSound(x) => 0xF018 | xy(x, 0),
+            AddI(x) => 0xF01E | xy(x, 0),
+            LoadFont(x) => 0xF029 | xy(x, 0),
+            LoadLargeFont(x) => 0xF030 | xy(x, 0),
+            Bcd(x) => 0xF033 | xy(x, 0),
+            Store(x) => 0xF055 | xy(x, 0),
+            Read(x) => 0xF065 | xy(x, 0),
+            SaveFlags(x) => 0xF075 | xy(x, 0),
+            LoadFlags(x) => 0xF085 | xy(x, 0),
+        }
+    }
+
+    pub fn size(self) -> usize {
+        match self {
+            Instruction::LoadLongI => 4,
+            _ => 2,
+        }
+    }
+
+    pub fn is_skip(self) -> bool {
+        matches!(
+            self,
+            Instruction::SkipEqByte { .. }
+                | Instruction::SkipNeByte { .. }
+                | Instruction::SkipEqReg { .. }
+                | Instruction::SkipNeReg { .. }
+                | Instruction::SkipKey(_)
+                | Instruction::SkipNotKey(_)
+        )
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use Instruction::*;
+
+        match *self {
+            Clear => write!(f, "CLS"),
+            Return => write!(f, "RET"),
+            ScrollDown(rows) => write!(f, "SCD {}", rows),
+            ScrollUp(rows) => write!(f, "SCU {}", rows),
+            ScrollRight => write!(f, "SCR"),
+            ScrollLeft => write!(f, "SCL"),
+            Exit => write!(f, "EXIT"),
+            Lores => write!(f, "LOW"),
+            Hires => write!(f, "HIGH"),
+            Jump(address) => write!(f, "JP 0x{:03X}", address),
+            Call(address) => write!(f, "CALL 0x{:03X}", address),
+            SkipEqByte { x, byte } => write!(f, "SE V{:X}, 0x{:02X}", x, byte),
+            SkipNeByte { x, byte } => write!(f, "SNE V{:X}, 0x{:02X}", x, byte),
+            SkipEqReg { x, y } => write!(f, "SE V{:X}, V{:X}", x, y),
+            SaveRange { x, y } => write!(f, "SAVE V{:X}, V{:X}", x, y),
+            LoadRange { x, y } => write!(f, "LOAD V{:X}, V{:X}", x, y),
+            LoadByte { x, byte } => write!(f, "LD V{:X}, 0x{:02X}", x, byte),
+            AddByte { x, byte } => write!(f, "ADD V{:X}, 0x{:02X}", x, byte),
+            LoadReg { x, y } => write!(f, "LD V{:X}, V{:X}", x, y),
+            Or { x, y } => write!(f, "OR V{:X}, V{:X}", x, y),
+            And { x, y } => write!(f, "AND V{:X}, V{:X}", x, y),
+            Xor { x, y } => write!(f, "XOR V{:X}, V{:X}", x, y),
+            AddReg { x, y } => write!(f, "ADD V{:X}, V{:X}", x, y),
+            Sub { x, y } => write!(f, "SUB V{:X}, V{:X}", x, y),
+            ShiftRight { x, y } => write!(f, "SHR V{:X}, V{:X}", x, y),
+            SubN { x, y } => write!(f, "SUBN V{:X}, V{:X}", x, y),
+            ShiftLeft { x, y } => write!(f, "SHL V{:X}, V{:X}", x, y),
+            SkipNeReg { x, y } => write!(f, "SNE V{:X}, V{:X}", x, y),
+            LoadI(address) => write!(f, "LD I, 0x{:03X}", address),
+            JumpV0(address) => write!(f, "JP V0, 0x{:03X}", address),
+            Random { x, byte } => write!(f, "RND V{:X}, 0x{:02X}", x, byte),
+            Draw { x, y, rows } => write!(f, "DRW V{:X}, V{:X}, {}", x, y, rows),
+            SkipKey(x) => write!(f, "SKP V{:X}", x),
+            SkipNotKey(x) => write!(f, "SKNP V{:X}", x),
+            LoadLongI => write!(f, "LD I, LONG"),
+            SelectPlanes(planes) => write!(f, "PLANE {}", planes),
+            LoadAudio => write!(f, "AUDIO"),
+            LoadPitch(x) => write!(f, "PITCH V{:X}", x),
+            LoadDelay(x) => write!(f, "LD V{:X}, DT", x),
+            WaitKey(x) => write!(f, "LD V{:X}, K", x),
+            SetDelay(x) => write!(f, "LD DT, V{:X}", x),
+            SetSound(x) => write!(f, "LD ST, V{:X}", x),
+            AddI(x) => write!(f, "ADD I, V{:X}", x),
+            LoadFont(x) => write!(f, "LD F, V{:X}", x),
+            LoadLargeFont(x) => write!(f, "LD HF, V{:X}", x),
+            Bcd(x) => write!(f, "LD B, V{:X}", x),
+            Store(x) => write!(f, "LD [I], V{:X}", x),
+            Read(x) => write!(f, "LD V{:X}, [I]", x),
+            SaveFlags(x) => write!(f, "LD R, V{:X}", x),
+            LoadFlags(x) => write!(f, "LD V{:X}, R", x),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Fetched {
+    pub opcode: u16,
+    pub operand: Option<u16>,
+    pub instruction: Option<Instruction>,
+}
+
+impl Fetched {
+    pub fn read_at(memory: &[u8], address: usize, platform: Platform) -> Option<Self> {
+        let word = |address: usize| {
+            memory
+                .get(address..address.checked_add(2)?)
+                .map(|bytes| (bytes[0] as u16) << 8 | bytes[1] as u16)
+        };
+        let opcode = word(address)?;
+        let instruction = Instruction::decode(opcode, platform).ok();
+        let operand = match instruction {
+            Some(Instruction::LoadLongI) => word(address + 2),
+            _ => None,
+        };
+        Some(Self {
+            opcode,
+            operand,
+            instruction,
+        })
+    }
+
+    pub fn size(&self) -> usize {
+        if self.operand.is_some() {
+            4
+        } else {
+            2
+        }
+    }
+}
+
+impl fmt::Display for Fetched {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.instruction, self.operand) {
+            (Some(Instruction::LoadLongI), Some(address)) => {
+                write!(f, "LD I, LONG 0x{:04X}", address)
+            }
+            (Some(instruction), _) => write!(f, "{}", instruction),
+            (None, _) => write!(f, "???"),
+        }
+    }
+}
@@ -1,19 +1,63 @@
 pub use display::DisplayBuffer;
-pub use ram::Ram;
+pub use ram::{Access, AddressPolicy, Ram, LARGE_FONT_START};
 
 mod ram {
     use std::ops::{Index, IndexMut};
     use std::slice::SliceIndex;
 
-    const SIZE: usize = 4096;
+    const SIZE: usize = 0x1000;
+
+    pub const LARGE_FONT_START: u16 = 0xA0;
+
+    const LARGE_FONT: [u8; 160] = [
+        0x3C, 0x7E, 0xE7, 0xC3, 0xC3, 0xC3, 0xC3, 0xE7, 0x7E, 0x3C, // 0
+        0x18, 0x38, 0x58, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x3C, // 1
+        0x3E, 0x7F, 0xC3, 0x06, 0x0C, 0x18, 0x30, 0x60, 0xFF, 0xFF, // 2
+        0x3C, 0x7E, 0xC3, 0x03, 0x0E, 0x0E, 0x03, 0xC3, 0x7E, 0x3C, // 3
+        0x06, 0x0E, 0x1E, 0x36, 0x66, 0xC6, 0xFF, 0xFF, 0x06, 0x06, // 4
+        0xFF, 0xFF, 0xC0, 0xC0, 0xFC, 0xFE, 0x03, 0xC3, 0x7E, 0x3C, // 5
+        0x3E, 0x7C, 0xE0, 0xC0, 0xFC, 0xFE, 0xC3, 0xC3, 0x7E, 0x3C, // 6
+        0xFF, 0xFF, 0x03, 0x06, 0x0C, 0x18, 0x30, 0x60, 0x60, 0x60, // 7
+        0x3C, 0x7E, 0xC3, 0xC3, 0x7E, 0x7E, 0xC3, 0xC3, 0x7E, 0x3C, // 8
+        0x3C, 0x7E, 0xC3, 0xC3, 0x7F, 0x3F, 0x03, 0x03, 0x3E, 0x7C, // 9
+        0x3C, 0x7E, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, // A
+        0xFC, 0xFE, 0xC3, 0xC3, 0xFE, 0xFE, 0xC3, 0xC3, 0xFE, 0xFC, // B
+        0x3C, 0x7E, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0x7E, 0x3C, // C
+        0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, // D
+        0xFF, 0xFF, 0xC0, 0xC0, 0xFC, 0xFC, 0xC0, 0xC0, 0xFF, 0xFF, // E
+        0xFF, 0xFF, 0xC0, 0xC0, 0xFC, 0xFC, 0xC0, 0xC0, 0xC0, 0xC0, // F
+    ];
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    pub enum Access {
+        Read(usize),
+        Write(usize),
+    }
+
+    impl Access {
+        pub fn address(self) -> usize {
+            match self {
+                Access::Read(address) | Access::Write(address) => address,
+            }
+        }
+    }
+
+    #[derive(Clone, Copy, Debug, Default, PartialEq)]
+    pub enum AddressPolicy {
+        Wrap,
+        #[default]
+        Fault,
+        Clamp,
+    }
 
     pub struct Ram {
-        data: [u8; SIZE],
+        data: Vec<u8>,
+        policy: AddressPolicy,
     }
 
-    impl Default for Ram {
-        fn default() -> Self {
-            let mut data = [0; SIZE];
+    impl Ram {
+        pub fn new(size: usize) -> Self {
+            let mut data = vec![0; size.max(SIZE)];
             data[0x50] = 0xF0;
             data[0x51] = 0x90;
             data[0x52] = 0x90;
@@ -110,7 +154,43 @@ mod ram {
             data[0x9E] = 0x80;
             data[0x9F] = 0x80;
 
-            Self { data }
+            let large_font = LARGE_FONT_START as usize;
+            data[large_font..large_font + LARGE_FONT.len()].copy_from_slice(&LARGE_FONT);
+
+            Self {
+                data,
+                policy: AddressPolicy::default(),
+            }
+        }
+
+        pub fn size(&self) -> usize {
+            self.data.len()
+        }
+
+        pub fn policy(&self) -> AddressPolicy {
+            self.policy
+        }
+
+        pub fn set_policy(&mut self, policy: AddressPolicy) {
+            self.policy = policy;
+        }
+
+        pub fn resolve(&self, address: usize) -> Option<usize> {
+            let size = self.size();
+            if address < size {
+                return Some(address);
+            }
+            match self.policy {
+                AddressPolicy::Wrap => Some(address % size),
+                AddressPolicy::Fault => None,
+                AddressPolicy::Clamp => Some(size - 1),
+            }
+        }
+    }
+
+    impl Default for Ram {
+        fn default() -> Self {
+            Self::new(SIZE)
         }
     }
 
@@ -136,34 +216,182 @@ mod ram {
 }
 
 pub mod display {
+    use std::fmt;
     use std::num::Wrapping;
 
-    pub const SIZE: usize = 2048;
-    pub const ROW_SIZE: usize = 64;
-    pub const COL_SIZE: usize = 32;
+    pub const LORES_WIDTH: usize = 64;
+    pub const LORES_HEIGHT: usize = 32;
+    pub const HIRES_WIDTH: usize = 128;
+    pub const HIRES_HEIGHT: usize = 64;
+    pub const HIRES_CHIP8_HEIGHT: usize = 64;
+
+    pub const PLANES: usize = 2;
 
+    #[derive(Clone)]
     pub struct DisplayBuffer {
-        pub data: [u8; SIZE],
+        pub data: Vec<u8>,
+        width: usize,
+        height: usize,
+        planes: u8,
     }
 
     impl DisplayBuffer {
+        pub fn new(width: usize, height: usize) -> Self {
+            Self {
+                data: vec![0; width * height],
+                width,
+                height,
+                planes: 1,
+            }
+        }
+
+        pub fn width(&self) -> usize {
+            self.width
+        }
+
+        pub fn height(&self) -> usize {
+            self.height
+        }
+
+        pub fn is_hires(&self) -> bool {
+            self.width == HIRES_WIDTH
+        }
+
+        pub fn planes(&self) -> u8 {
+            self.planes
+        }
+
+        pub fn select_planes(&mut self, planes: u8) {
+            self.planes = planes & ((1 << PLANES) - 1);
+        }
+
+        pub fn selected_plane_count(&self) -> usize {
+            self.planes.count_ones() as usize
+        }
+
+        pub fn set_hires(&mut self, hires: bool) {
+            if hires {
+                self.resize(HIRES_WIDTH, HIRES_HEIGHT);
+            } else {
+                self.resize(LORES_WIDTH, LORES_HEIGHT);
+            }
+        }
+
         pub fn clear(&mut self) {
-            self.data = [0; SIZE];
+            let planes = self.planes;
+            self.data.iter_mut().for_each(|pixel| *pixel &= !planes);
+        }
+
+        pub fn draw_sprite(&mut self, x: u8, y: u8, pixels: &[u8], clip: bool) -> Wrapping<u8> {
+            let rows = pixels.iter().map(|row| (*row as u16) << 8).collect();
+            self.draw_planes(x, y, rows, 8, clip)
+        }
+
+        pub fn draw_large_sprite(
+            &mut self,
+            x: u8,
+            y: u8,
+            pixels: &[u8],
+            clip: bool,
+        ) -> Wrapping<u8> {
+            let rows = pixels
+                .chunks(2)
+                .map(|row| (row[0] as u16) << 8 | *row.get(1).unwrap_or(&0) as u16)
+                .collect();
+            self.draw_planes(x, y, rows, 16, clip)
+        }
+
+        pub fn scroll_up(&mut self, rows: usize) {
+            self.scroll(0, -(rows as isize));
         }
 
-        pub fn draw_sprite(&mut self, x: u8, y: u8, pixels: &[u8]) -> Wrapping<u8> {
-            assert_eq!(pixels.len(), pixels.len());
-            let index = (x as usize) + (y as usize * ROW_SIZE);
+        pub fn scroll_down(&mut self, rows: usize) {
+            self.scroll(0, rows as isize);
+        }
+
+        pub fn scroll_left(&mut self, columns: usize) {
+            self.scroll(-(columns as isize), 0);
+        }
+
+        pub fn scroll_right(&mut self, columns: usize) {
+            self.scroll(columns as isize, 0);
+        }
+
+        fn scroll(&mut self, dx: isize, dy: isize) {
+            let (width, height) = (self.width as isize, self.height as isize);
+            let mut data = self.data.clone();
+            for y in 0..height {
+                for x in 0..width {
+                    let (from_x, from_y) = (x - dx, y - dy);
+                    let inside = (0..width).contains(&from_x) && (0..height).contains(&from_y);
+                    let moved = if inside {
+                        self.data[(from_y * width + from_x) as usize] & self.planes
+                    } else {
+                        0
+                    };
+                    let index = (y * width + x) as usize;
+                    data[index] = (self.data[index] & !self.planes) | moved;
+                }
+            }
+            self.data = data;
+        }
+
+        fn draw_planes(
+            &mut self,
+            x: u8,
+            y: u8,
+            rows: Vec<u16>,
+            sprite_width: usize,
+            clip: bool,
+        ) -> Wrapping<u8> {
+            let length = rows.len() / self.selected_plane_count().max(1);
+            let mut sprites = rows.chunks(length.max(1));
+            let mut flag = Wrapping(0);
+            for plane in (0..PLANES).map(|plane| 1 << plane) {
+                if self.planes & plane == 0 {
+                    continue;
+                }
+                let sprite = sprites.next().unwrap_or(&[]);
+                flag |= self.draw_rows(x, y, sprite, sprite_width, clip, plane);
+            }
+
+            flag
+        }
+
+        fn resize(&mut self, width: usize, height: usize) {
+            self.width = width;
+            self.height = height;
+            self.data = vec![0; width * height];
+        }
+
+        fn draw_rows(
+            &mut self,
+            x: u8,
+            y: u8,
+            rows: &[u16],
+            sprite_width: usize,
+            clip: bool,
+            plane: u8,
+        ) -> Wrapping<u8> {
+            let x = x as usize % self.width;
+            let y = y as usize % self.height;
             let mut flag: u8 = 0;
-            for (i, pixel) in pixels.iter().enumerate() {
-                let index = i * ROW_SIZE + index;
-                for j in 0..8 {
-                    let bit_shift = 7 - j;
-                    let bit = (pixel & (0x1 << bit_shift)) >> bit_shift;
-                    if self.data[index + j] & bit > 0 {
+            for (i, pixel) in rows.iter().enumerate() {
+                if clip && y + i >= self.height {
+                    break;
+                }
+                let row = (y + i) % self.height;
+                for j in 0..sprite_width {
+                    if clip && x + j >= self.width {
+                        break;
+                    }
+                    let index = row * self.width + (x + j) % self.width;
+                    let bit_shift = 15 - j;
+                    let bit = ((pixel >> bit_shift) & 0x1) as u8 * plane;
+                    if self.data[index] & bit > 0 {
                         flag = 1;
                     }
-                    self.data[index + j] ^= bit;
+                    self.data[index] ^= bit;
                 }
             }
 
@@ -171,9 +399,21 @@ pub mod display {
         }
     }
 
+    impl fmt::Display for DisplayBuffer {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            for row in self.data.chunks(self.width) {
+                for pixel in row {
+                    write!(f, "{}", ['.', '#', '+', '@'][*pixel as usize & 0x3])?;
+                }
+                writeln!(f)?;
+            }
+            Ok(())
+        }
+    }
+
     impl Default for DisplayBuffer {
         fn default() -> Self {
-            Self { data: [0; SIZE] }
+            Self::new(LORES_WIDTH, LORES_HEIGHT)
         }
     }
 }
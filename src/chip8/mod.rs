@@ -1,335 +1,904 @@
+pub mod error;
+pub mod instruction;
 pub mod memory;
+pub mod platform;
+pub mod quirks;
+pub mod rewind;
+pub mod state;
+pub mod trace;
 
 use crate::bitwise::*;
-use crate::frontend::FrontEnd;
-use memory::{DisplayBuffer, Ram};
+use crate::frontend::{DebugInfo, FrontEnd, Keys, Request};
+pub use error::VmError;
+pub use instruction::{DecodeError, Fetched, Instruction};
+pub use memory::Access;
+use memory::{AddressPolicy, DisplayBuffer, Ram, LARGE_FONT_START};
+pub use platform::Platform;
+pub use quirks::Quirks;
+pub use rewind::{RewindBuffer, DEFAULT_REWIND_FRAMES};
+pub use state::StateHeader;
+pub use trace::Tracer;
 
 use rand::prelude::*;
-use std::io::Error as IOError;
+use rand_chacha::ChaCha20Rng;
+use std::fs;
+use std::io::{ErrorKind, Read};
 use std::num::Wrapping;
-use std::time::{Duration, Instant};
-use std::{fs, thread};
+use std::path::{Path, PathBuf};
 
-const PROGRAM_START: u16 = 0x200;
-const FRAME_DURATION: Duration = Duration::from_micros(1429);
-const TIMER_DELAY: Duration = Duration::from_micros(16667);
+pub const MAX_STACK_DEPTH: usize = 16;
+pub const MAX_RPL_FLAGS: usize = 16;
+pub const AUDIO_PATTERN_SIZE: usize = 16;
+pub const DEFAULT_PITCH: u8 = 64;
+
+pub const HIRES_CHIP8_JUMP: u16 = 0x260;
+pub const HIRES_CHIP8_ENTRY: u16 = 0x2C0;
+pub const HIRES_CHIP8_CLEAR: u16 = 0x230;
+pub const DEFAULT_CPU_HZ: u32 = 700;
+pub const FRAME_HZ: u32 = 60;
 
 pub struct Chip8Vm {
     ram: Ram,
     display_data: DisplayBuffer,
     draw_flag: bool,
+    audio_flag: bool,
     jump_flag: bool,
     frontend: Box<dyn FrontEnd>,
+    keys: Keys,
+    platform: Platform,
+    quirks: Quirks,
+    exited: bool,
+    buzzer: bool,
     v: [Wrapping<u8>; 16],
     dt: u8,
     st: u8,
     i: u16,
     pc: u16,
     sp: usize,
-    stack: [u16; 16],
-    rng: ThreadRng,
-    prev_timer_delay: Instant,
+    stack: [u16; MAX_STACK_DEPTH],
+    stack_depth: usize,
+    rpl: [u8; MAX_RPL_FLAGS],
+    rpl_path: Option<PathBuf>,
+    audio_pattern: [u8; AUDIO_PATTERN_SIZE],
+    pitch: u8,
+    rng: RngSource,
+    rom_hash: u64,
+    history: RewindBuffer,
+    track_accesses: bool,
+    accesses: Vec<Access>,
+    tracer: Option<Tracer>,
 }
 
 impl Chip8Vm {
-    pub fn new(frontend: Box<dyn FrontEnd>) -> Chip8Vm {
+    pub fn new(frontend: Box<dyn FrontEnd>, quirks: Quirks) -> Chip8Vm {
+        Self::with_platform(frontend, Platform::Chip8, quirks)
+    }
+
+    pub fn with_seed(
+        frontend: Box<dyn FrontEnd>,
+        platform: Platform,
+        quirks: Quirks,
+        seed: u64,
+    ) -> Chip8Vm {
+        let mut vm = Self::with_platform(frontend, platform, quirks);
+        vm.set_seed(seed);
+        vm
+    }
+
+    pub fn with_platform(
+        frontend: Box<dyn FrontEnd>,
+        platform: Platform,
+        quirks: Quirks,
+    ) -> Chip8Vm {
+        let seed = random();
         Chip8Vm {
-            ram: Ram::default(),
-            display_data: DisplayBuffer::default(),
+            ram: Ram::new(platform.memory_size()),
+            display_data: {
+                let (width, height) = platform.display_size();
+                DisplayBuffer::new(width, height)
+            },
             draw_flag: false,
+            audio_flag: false,
             jump_flag: false,
             frontend,
+            keys: Keys::default(),
+            platform,
+            quirks,
+            exited: false,
+            buzzer: false,
             v: [Wrapping(0); 16],
             dt: 0,
             st: 0,
             i: 0,
-            pc: PROGRAM_START,
+            pc: platform.program_start(),
             sp: 0,
-            stack: [0; 16],
-            rng: rand::thread_rng(),
-            prev_timer_delay: Instant::now(),
+            stack: [0; MAX_STACK_DEPTH],
+            stack_depth: MAX_STACK_DEPTH,
+            rpl: [0; MAX_RPL_FLAGS],
+            rpl_path: None,
+            audio_pattern: [0; AUDIO_PATTERN_SIZE],
+            pitch: DEFAULT_PITCH,
+            rng: RngSource::seeded(seed, 0),
+            rom_hash: state::hash_rom(&[]),
+            history: RewindBuffer::new(0),
+            track_accesses: false,
+            accesses: Vec::new(),
+            tracer: None,
         }
     }
 
-    pub fn load_game(&mut self, filename: &str) -> Result<(), IOError> {
-        let file_contents = fs::read("roms/".to_owned() + filename)?;
-        for (i, byte) in file_contents.iter().enumerate() {
-            self.ram[i + PROGRAM_START as usize] = *byte;
+    pub fn set_stack_depth(&mut self, depth: usize) {
+        self.stack_depth = depth.min(MAX_STACK_DEPTH);
+    }
+
+    pub fn set_seed(&mut self, seed: u64) {
+        self.restore_rng(seed, 0);
+    }
+
+    pub fn set_rng(&mut self, rng: Box<dyn RngCore>) {
+        self.rng = RngSource::Injected(rng);
+    }
+
+    pub fn seed(&self) -> Option<u64> {
+        match &self.rng {
+            RngSource::Seeded { seed, .. } => Some(*seed),
+            RngSource::Injected(_) => None,
         }
+    }
+
+    pub fn rng_position(&self) -> u128 {
+        match &self.rng {
+            RngSource::Seeded { rng, .. } => rng.get_word_pos(),
+            RngSource::Injected(_) => 0,
+        }
+    }
+
+    pub fn restore_rng(&mut self, seed: u64, position: u128) {
+        self.rng = RngSource::seeded(seed, position);
+    }
+
+    pub fn set_rpl_path(&mut self, path: impl Into<PathBuf>) -> Result<(), VmError> {
+        let path = path.into();
+        match fs::read(&path) {
+            Ok(flags) => {
+                let count = flags.len().min(MAX_RPL_FLAGS);
+                self.rpl[..count].copy_from_slice(&flags[..count]);
+            }
+            Err(error) if error.kind() == ErrorKind::NotFound => {}
+            Err(error) => return Err(error.into()),
+        }
+        self.rpl_path = Some(path);
 
         Ok(())
     }
 
-    pub fn execute_cycle(&mut self) -> Result<(), &'static str> {
-        let time = Instant::now();
-        let pc = self.pc as usize;
-        let opcode: u16 = (self.ram[pc] as u16) << 8 | (self.ram[pc + 1] as u16);
+    pub fn set_rewind_frames(&mut self, frames: usize) {
+        self.history = RewindBuffer::new(frames);
+    }
+
+    pub fn rewind(&mut self, frames: usize) -> Result<usize, VmError> {
+        let mut rewound = 0;
+        if let Some((frames, state)) = self.history.rewind(frames) {
+            let state = state.to_vec();
+            self.load_state(&state)?;
+            self.frontend.rewind(frames);
+            rewound = frames;
+        }
+        self.frontend.update();
+
+        Ok(rewound)
+    }
+
+    pub fn set_address_policy(&mut self, policy: AddressPolicy) {
+        self.ram.set_policy(policy);
+    }
+
+    pub fn load_rom_bytes(&mut self, rom: &[u8]) -> Result<(), VmError> {
+        let start = self.platform.program_start() as usize;
+        let max = self.ram.size() - start;
+        if rom.len() > max {
+            return Err(VmError::RomTooLarge {
+                size: rom.len(),
+                max,
+            });
+        }
+        self.ram[start..start + rom.len()].copy_from_slice(rom);
+        self.rom_hash = state::hash_rom(rom);
+
+        Ok(())
+    }
+
+    pub fn load_rom_path(&mut self, path: impl AsRef<Path>) -> Result<(), VmError> {
+        self.load_rom_bytes(&fs::read(path)?)
+    }
+
+    pub fn load_rom_reader(&mut self, mut reader: impl Read) -> Result<(), VmError> {
+        let mut rom = Vec::new();
+        reader.read_to_end(&mut rom)?;
+        self.load_rom_bytes(&rom)
+    }
+
+    pub fn run_frame(&mut self, cycles_per_frame: u32) -> Result<(), VmError> {
+        self.begin_frame();
+        for _ in 0..cycles_per_frame {
+            if self.exited {
+                break;
+            }
+            self.step()?;
+        }
+        self.end_frame();
+
+        Ok(())
+    }
+
+    pub fn begin_frame(&mut self) {
+        self.keys = self.frontend.get_keys().clone();
         self.draw_flag = false;
-        self.jump_flag = false;
+    }
 
-        let unknown_opcode = Err("Unknown opcode");
-        let end = opcode & 0xFFF;
-        match (opcode & 0xF000) >> 12 {
-            0x0 => match opcode & 0xFF {
-                0xE0 => self.clear(end),
-                0xEE => self.ret(end),
-                _ => return unknown_opcode,
-            },
-            0x1 => self.jump(end),
-            0x2 => self.call(end),
-            0x3 => self.skip_b_eq(end),
-            0x4 => self.skip_b_ne(end),
-            0x5 => self.skip_y_eq(end),
-            0x6 => self.put_x_b(end),
-            0x7 => self.add_x_b(end),
-            0x8 => match opcode & 0xF {
-                0x0 => self.put_x_y(end),
-                0x1 => self.or(end),
-                0x2 => self.and(end),
-                0x3 => self.xor(end),
-                0x4 => self.add_x_y(end),
-                0x5 => self.sub(end),
-                0x6 => self.shr(end),
-                0x7 => self.subn(end),
-                0xE => self.shl(end),
-                _ => return unknown_opcode,
-            },
-            0x9 => self.skip_y_ne(end),
-            0xA => self.put_i_addr(end),
-            0xB => self.jump_0(end),
-            0xC => self.rand(end),
-            0xD => self.draw(end),
-            0xE => match opcode & 0xFF {
-                0x9E => self.skip_key(end),
-                0xA1 => self.skip_not_key(end),
-                _ => return unknown_opcode,
-            },
-            0xF => match opcode & 0xFF {
-                0x07 => self.put_x_dt(end),
-                0x0A => self.wait_for_keypress(end),
-                0x15 => self.put_dt_x(end),
-                0x18 => self.put_st_x(end),
-                0x1E => self.add_i_x(end),
-                0x29 => self.sprite_addr(end),
-                0x33 => self.bcd(end),
-                0x55 => self.store(end),
-                0x65 => self.read(end),
-                _ => return unknown_opcode,
-            },
-            _ => return unknown_opcode,
-        };
+    pub fn end_frame(&mut self) {
+        if self.audio_flag {
+            self.audio_flag = false;
+            self.frontend
+                .set_audio_pattern(&self.audio_pattern, self.pitch);
+        }
+        if self.buzzer != (self.st > 0) {
+            self.buzzer = self.st > 0;
+            self.frontend.set_buzzer(self.buzzer);
+        }
+        self.dt = self.dt.saturating_sub(1);
+        self.st = self.st.saturating_sub(1);
 
         if self.draw_flag {
             self.frontend.draw(&self.display_data);
         }
+        self.show_debug_info(false);
+        self.frontend.end_frame();
         self.frontend.update();
+        if self.history.capacity() > 0 {
+            self.history.push(self.save_state());
+        }
+    }
 
-        if !self.jump_flag {
-            self.pc += 2;
+    pub fn idle(&mut self) {
+        if self.draw_flag {
+            self.draw_flag = false;
+            self.frontend.draw(&self.display_data);
         }
+        self.show_debug_info(true);
+        self.frontend.update();
+    }
 
-        if self.prev_timer_delay.elapsed() > TIMER_DELAY {
-            self.prev_timer_delay = Instant::now();
-            if self.dt > 0 {
-                self.dt -= 1;
-            }
-            if self.st > 0 {
-                self.st -= 1;
+    fn show_debug_info(&mut self, paused: bool) {
+        if !self.frontend.wants_debug_info() {
+            return;
+        }
+        let info = DebugInfo {
+            platform: self.platform,
+            v: self.v(),
+            i: self.i,
+            pc: self.pc,
+            stack: &self.stack[..self.sp],
+            dt: self.dt,
+            st: self.st,
+            keys: &self.keys,
+            memory: &self.ram[..],
+            paused,
+        };
+        self.frontend.draw_debug_info(&info);
+    }
+
+    pub fn step(&mut self) -> Result<(), VmError> {
+        let mut tracer = self.tracer.take();
+        let before = tracer.as_ref().map(|_| trace::Snapshot::new(self));
+        let mut result = self.execute_next();
+        if let (Some(tracer), Some(before)) = (&mut tracer, before) {
+            if let Err(error) = tracer.record(&before, self, &result) {
+                result = result.and(Err(error.into()));
             }
         }
+        self.tracer = tracer;
+
+        result
+    }
+
+    fn execute_next(&mut self) -> Result<(), VmError> {
+        let pc = self.pc;
+        let instruction = self.next_instruction()?;
+        self.jump_flag = false;
+        self.accesses.clear();
+        self.execute(instruction)?;
+
+        if !self.jump_flag {
+            self.pc = self
+                .pc
+                .checked_add(2)
+                .ok_or(VmError::PcOutOfBounds { pc })?;
+        }
 
-        if time.elapsed() < FRAME_DURATION {
-            thread::sleep(FRAME_DURATION - time.elapsed());
+        Ok(())
+    }
+
+    pub fn next_instruction(&self) -> Result<Instruction, VmError> {
+        let pc = self.pc as usize;
+        if pc + 1 >= self.ram.size() {
+            return Err(VmError::PcOutOfBounds { pc: self.pc });
+        }
+        let opcode: u16 = (self.ram[pc] as u16) << 8 | (self.ram[pc + 1] as u16);
+        Instruction::decode(opcode, self.platform).map_err(|_| VmError::UnknownOpcode {
+            opcode,
+            pc: self.pc,
+        })
+    }
+
+    fn execute(&mut self, instruction: Instruction) -> Result<(), VmError> {
+        use Instruction::*;
+
+        match instruction {
+            Clear => self.clear(),
+            Return => self.ret()?,
+            ScrollDown(rows) => self.scroll_down(rows),
+            ScrollUp(rows) => self.scroll_up(rows),
+            ScrollRight => self.scroll_right(),
+            ScrollLeft => self.scroll_left(),
+            Exit => self.exit(),
+            Lores => self.lores(),
+            Hires => self.hires(),
+            Jump(address) => self.jump(address),
+            Call(address) => self.call(address)?,
+            SkipEqByte { x, byte } => self.skip_b_eq(x, byte)?,
+            SkipNeByte { x, byte } => self.skip_b_ne(x, byte)?,
+            SkipEqReg { x, y } => self.skip_y_eq(x, y)?,
+            SaveRange { x, y } => self.save_range(x, y)?,
+            LoadRange { x, y } => self.load_range(x, y)?,
+            LoadByte { x, byte } => self.put_x_b(x, byte),
+            AddByte { x, byte } => self.add_x_b(x, byte),
+            LoadReg { x, y } => self.put_x_y(x, y),
+            Or { x, y } => self.or(x, y),
+            And { x, y } => self.and(x, y),
+            Xor { x, y } => self.xor(x, y),
+            AddReg { x, y } => self.add_x_y(x, y),
+            Sub { x, y } => self.sub(x, y),
+            ShiftRight { x, y } => self.shr(x, y),
+            SubN { x, y } => self.subn(x, y),
+            ShiftLeft { x, y } => self.shl(x, y),
+            SkipNeReg { x, y } => self.skip_y_ne(x, y)?,
+            LoadI(address) => self.put_i_addr(address),
+            JumpV0(address) => self.jump_0(address),
+            Random { x, byte } => self.rand(x, byte),
+            Draw { x, y, rows } => self.draw(x, y, rows)?,
+            SkipKey(x) => self.skip_key(x)?,
+            SkipNotKey(x) => self.skip_not_key(x)?,
+            LoadLongI => self.put_i_long()?,
+            SelectPlanes(planes) => self.select_planes(planes),
+            LoadAudio => self.load_audio_pattern()?,
+            LoadPitch(x) => self.put_pitch_x(x),
+            LoadDelay(x) => self.put_x_dt(x),
+            WaitKey(x) => self.wait_for_keypress(x),
+            SetDelay(x) => self.put_dt_x(x),
+            SetSound(x) => self.put_st_x(x),
+            AddI(x) => self.add_i_x(x),
+            LoadFont(x) => self.sprite_addr(x),
+            LoadLargeFont(x) => self.large_sprite_addr(x),
+            Bcd(x) => self.bcd(x)?,
+            Store(x) => self.store(x)?,
+            Read(x) => self.read(x)?,
+            SaveFlags(x) => self.save_flags(x)?,
+            LoadFlags(x) => self.load_flags(x),
         }
 
         Ok(())
     }
 
+    pub fn display(&self) -> &DisplayBuffer {
+        &self.display_data
+    }
+
+    pub fn keys(&self) -> &Keys {
+        &self.keys
+    }
+
+    pub fn platform(&self) -> Platform {
+        self.platform
+    }
+
+    pub fn audio_pattern(&self) -> &[u8; AUDIO_PATTERN_SIZE] {
+        &self.audio_pattern
+    }
+
+    pub fn pitch(&self) -> u8 {
+        self.pitch
+    }
+
+    pub fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    pub fn set_pc(&mut self, pc: u16) {
+        self.pc = pc;
+    }
+
+    pub fn i(&self) -> u16 {
+        self.i
+    }
+
+    pub fn set_i(&mut self, i: u16) {
+        self.i = i;
+    }
+
+    pub fn v(&self) -> [u8; 16] {
+        let mut v = [0; 16];
+        for (value, register) in v.iter_mut().zip(&self.v) {
+            *value = register.0;
+        }
+        v
+    }
+
+    pub fn set_v(&mut self, x: usize, value: u8) {
+        self.v[x & 0xF] = Wrapping(value);
+    }
+
+    pub fn delay_timer(&self) -> u8 {
+        self.dt
+    }
+
+    pub fn set_delay_timer(&mut self, value: u8) {
+        self.dt = value;
+    }
+
+    pub fn sound_timer(&self) -> u8 {
+        self.st
+    }
+
+    pub fn set_sound_timer(&mut self, value: u8) {
+        self.st = value;
+    }
+
+    pub fn stack(&self) -> &[u16] {
+        &self.stack[..self.sp]
+    }
+
+    pub fn memory(&self) -> &[u8] {
+        &self.ram[..]
+    }
+
+    pub fn memory_mut(&mut self) -> &mut [u8] {
+        &mut self.ram[..]
+    }
+
+    pub fn set_tracer(&mut self, tracer: Tracer) {
+        self.tracer = Some(tracer);
+    }
+
+    pub fn take_tracer(&mut self) -> Option<Tracer> {
+        self.tracer.take()
+    }
+
+    pub fn set_access_tracking(&mut self, enabled: bool) {
+        self.track_accesses = enabled;
+        self.accesses.clear();
+    }
+
+    pub fn accesses(&self) -> &[Access] {
+        &self.accesses
+    }
+
+    pub fn has_exited(&self) -> bool {
+        self.exited
+    }
+
     pub fn should_quit(&self) -> bool {
-        self.frontend.should_quit()
+        self.exited || self.frontend.should_quit()
+    }
+
+    pub fn poll_request(&mut self) -> Option<Request> {
+        self.frontend.poll_request()
+    }
+
+    fn i_address(&self, offset: usize) -> Result<usize, VmError> {
+        let address = self.i as usize + offset;
+        self.ram.resolve(address).ok_or(VmError::MemoryOutOfBounds {
+            address,
+            pc: self.pc,
+        })
     }
 
-    fn clear(&mut self, _: u16) {
+    fn read_ram(&mut self, address: usize) -> u8 {
+        if self.track_accesses {
+            self.accesses.push(Access::Read(address));
+        }
+        self.ram[address]
+    }
+
+    fn write_ram(&mut self, address: usize, value: u8) {
+        if self.track_accesses {
+            self.accesses.push(Access::Write(address));
+        }
+        self.ram[address] = value;
+    }
+
+    fn clear(&mut self) {
         self.display_data.clear();
     }
 
-    fn ret(&mut self, _: u16) {
+    fn scroll_down(&mut self, rows: u8) {
+        self.display_data.scroll_down(rows as usize);
+        self.draw_flag = true;
+    }
+
+    fn scroll_up(&mut self, rows: u8) {
+        self.display_data.scroll_up(rows as usize);
+        self.draw_flag = true;
+    }
+
+    fn scroll_right(&mut self) {
+        self.display_data.scroll_right(4);
+        self.draw_flag = true;
+    }
+
+    fn scroll_left(&mut self) {
+        self.display_data.scroll_left(4);
+        self.draw_flag = true;
+    }
+
+    fn exit(&mut self) {
+        self.exited = true;
+        self.jump_flag = true;
+    }
+
+    fn lores(&mut self) {
+        self.display_data.set_hires(false);
+        self.draw_flag = true;
+    }
+
+    fn hires(&mut self) {
+        self.display_data.set_hires(true);
+        self.draw_flag = true;
+    }
+
+    fn ret(&mut self) -> Result<(), VmError> {
+        if self.sp == 0 {
+            return Err(VmError::StackUnderflow {
+                pc: self.pc,
+                stack: self.stack().to_vec(),
+            });
+        }
         self.sp -= 1;
         self.pc = self.stack[self.sp];
+        self.stack[self.sp] = 0;
+        Ok(())
     }
 
-    fn jump(&mut self, op: u16) {
+    fn jump(&mut self, address: u16) {
+        let hires_start = self.platform.supports_hires_chip8()
+            && self.pc == self.platform.program_start()
+            && address == HIRES_CHIP8_JUMP;
         self.jump_flag = true;
-        self.pc = get_address(op);
+        self.pc = if hires_start {
+            HIRES_CHIP8_ENTRY
+        } else {
+            address
+        };
     }
 
-    fn jump_0(&mut self, op: u16) {
+    fn jump_0(&mut self, address: u16) {
+        let offset = if self.quirks.jump_uses_vx {
+            self.v[get_x(address)]
+        } else {
+            self.v[0]
+        };
         self.jump_flag = true;
-        self.pc = get_address(op) + self.v[0].0 as u16;
+        self.pc = address + offset.0 as u16;
     }
 
-    fn call(&mut self, op: u16) {
+    fn call(&mut self, address: u16) -> Result<(), VmError> {
+        if self.sp >= self.stack_depth {
+            return Err(VmError::StackOverflow {
+                pc: self.pc,
+                stack: self.stack().to_vec(),
+            });
+        }
         self.jump_flag = true;
         self.stack[self.sp] = self.pc;
-        self.pc = get_address(op);
+        self.pc = address;
         self.sp += 1;
+        Ok(())
     }
 
-    fn skip_b_eq(&mut self, op: u16) {
-        if self.v[get_x(op)].0 == get_byte(op) {
-            self.pc += 2;
+    fn skip_if(&mut self, condition: bool) -> Result<(), VmError> {
+        if !condition {
+            return Ok(());
         }
+        let next = self.pc as usize + 2;
+        let long = self.platform.supports_xochip()
+            && next + 1 < self.ram.size()
+            && self.ram[next] == 0xF0
+            && self.ram[next + 1] == 0x00;
+        self.advance_pc(if long { 4 } else { 2 })
+    }
+
+    fn advance_pc(&mut self, offset: u16) -> Result<(), VmError> {
+        self.pc = self
+            .pc
+            .checked_add(offset)
+            .ok_or(VmError::PcOutOfBounds { pc: self.pc })?;
+        Ok(())
     }
 
-    fn skip_b_ne(&mut self, op: u16) {
-        if self.v[get_x(op)].0 != get_byte(op) {
-            self.pc += 2;
-        }
+    fn skip_b_eq(&mut self, x: usize, byte: u8) -> Result<(), VmError> {
+        self.skip_if(self.v[x].0 == byte)
     }
 
-    fn skip_y_eq(&mut self, op: u16) {
-        if self.v[get_x(op)] == self.v[get_y(op)] {
-            self.pc += 2;
-        }
+    fn skip_b_ne(&mut self, x: usize, byte: u8) -> Result<(), VmError> {
+        self.skip_if(self.v[x].0 != byte)
     }
 
-    fn skip_y_ne(&mut self, op: u16) {
-        if self.v[get_x(op)] != self.v[get_y(op)] {
-            self.pc += 2;
-        }
+    fn skip_y_eq(&mut self, x: usize, y: usize) -> Result<(), VmError> {
+        self.skip_if(self.v[x] == self.v[y])
     }
 
-    fn skip_key(&mut self, op: u16) {
-        if self.frontend.get_keys()[self.v[get_x(op)].0 as usize].0 {
-            self.pc += 2;
-        }
+    fn skip_y_ne(&mut self, x: usize, y: usize) -> Result<(), VmError> {
+        self.skip_if(self.v[x] != self.v[y])
     }
 
-    fn skip_not_key(&mut self, op: u16) {
-        if !self.frontend.get_keys()[self.v[get_x(op)].0 as usize].0 {
-            self.pc += 2;
-        }
+    fn skip_key(&mut self, x: usize) -> Result<(), VmError> {
+        self.skip_if(self.keys[self.v[x].0 as usize & 0xF].0)
     }
 
-    fn put_x_b(&mut self, op: u16) {
-        self.v[get_x(op)] = Wrapping(get_byte(op));
+    fn skip_not_key(&mut self, x: usize) -> Result<(), VmError> {
+        self.skip_if(!self.keys[self.v[x].0 as usize & 0xF].0)
     }
 
-    fn put_x_y(&mut self, op: u16) {
-        self.v[get_x(op)] = self.v[get_y(op)];
+    fn put_x_b(&mut self, x: usize, byte: u8) {
+        self.v[x] = Wrapping(byte);
     }
 
-    fn put_i_addr(&mut self, op: u16) {
-        self.i = get_address(op);
+    fn put_x_y(&mut self, x: usize, y: usize) {
+        self.v[x] = self.v[y];
     }
 
-    fn put_x_dt(&mut self, op: u16) {
-        self.v[get_x(op)] = Wrapping(self.dt);
+    fn put_i_addr(&mut self, address: u16) {
+        self.i = address;
+    }
+
+    fn put_i_long(&mut self) -> Result<(), VmError> {
+        let address = self.pc as usize + 2;
+        if address + 1 >= self.ram.size() {
+            return Err(VmError::PcOutOfBounds { pc: self.pc });
+        }
+        self.i = (self.ram[address] as u16) << 8 | self.ram[address + 1] as u16;
+        self.advance_pc(2)
     }
 
-    fn put_dt_x(&mut self, op: u16) {
-        self.dt = self.v[get_x(op)].0;
+    fn put_pitch_x(&mut self, x: usize) {
+        self.pitch = self.v[x].0;
+        self.audio_flag = true;
     }
 
-    fn put_st_x(&mut self, op: u16) {
-        self.st = self.v[get_x(op)].0;
+    fn put_x_dt(&mut self, x: usize) {
+        self.v[x] = Wrapping(self.dt);
     }
 
-    fn add_x_b(&mut self, op: u16) {
-        self.v[get_x(op)] += Wrapping(get_byte(op));
+    fn put_dt_x(&mut self, x: usize) {
+        self.dt = self.v[x].0;
     }
 
-    fn add_x_y(&mut self, op: u16) {
-        self.v[0xF] = Wrapping(
-            (self.v[get_x(op)].0 as u16 + self.v[get_y(op)].0 as u16 > u8::max_value() as u16)
-                as u8,
-        );
-        self.v[get_x(op)] += self.v[get_y(op)];
+    fn put_st_x(&mut self, x: usize) {
+        self.st = self.v[x].0;
     }
 
-    fn add_i_x(&mut self, op: u16) {
-        self.i += self.v[get_x(op)].0 as u16;
+    fn add_x_b(&mut self, x: usize, byte: u8) {
+        self.v[x] += Wrapping(byte);
     }
 
-    fn or(&mut self, op: u16) {
-        self.v[get_x(op)] |= self.v[get_y(op)];
+    fn add_x_y(&mut self, x: usize, y: usize) {
+        self.v[0xF] = Wrapping((self.v[x].0 as u16 + self.v[y].0 as u16 > u8::MAX as u16) as u8);
+        self.v[x] += self.v[y];
     }
 
-    fn and(&mut self, op: u16) {
-        self.v[get_x(op)] &= self.v[get_y(op)];
+    fn add_i_x(&mut self, x: usize) {
+        self.i = self.i.wrapping_add(self.v[x].0 as u16);
     }
 
-    fn xor(&mut self, op: u16) {
-        self.v[get_x(op)] ^= self.v[get_y(op)];
+    fn or(&mut self, x: usize, y: usize) {
+        self.v[x] |= self.v[y];
+        self.reset_vf();
     }
 
-    fn sub(&mut self, op: u16) {
-        let x = get_x(op);
-        let y = get_y(op);
+    fn and(&mut self, x: usize, y: usize) {
+        self.v[x] &= self.v[y];
+        self.reset_vf();
+    }
+
+    fn xor(&mut self, x: usize, y: usize) {
+        self.v[x] ^= self.v[y];
+        self.reset_vf();
+    }
+
+    fn sub(&mut self, x: usize, y: usize) {
         self.v[0xF] = Wrapping((self.v[x] > self.v[y]) as u8);
         self.v[x] -= self.v[y];
     }
 
-    fn subn(&mut self, op: u16) {
-        let x = get_x(op);
-        let y = get_y(op);
-        self.v[0xF] = Wrapping((self.v[get_x(op)] < self.v[get_y(op)]) as u8);
+    fn subn(&mut self, x: usize, y: usize) {
+        self.v[0xF] = Wrapping((self.v[x] < self.v[y]) as u8);
         self.v[x] = self.v[y] - self.v[x];
     }
 
-    fn shr(&mut self, op: u16) {
-        let x = get_x(op);
-        self.v[0xF] = Wrapping(self.v[x].0 & 0x1);
-        self.v[x] >>= 1;
+    fn shr(&mut self, x: usize, y: usize) {
+        self.v[0xF] = self.shift_source(x, y) & Wrapping(0x1);
+        self.v[x] = self.shift_source(x, y) >> 1;
+    }
+
+    fn shl(&mut self, x: usize, y: usize) {
+        self.v[0xF] = self.shift_source(x, y) >> 7;
+        self.v[x] = self.shift_source(x, y) << 1;
+    }
+
+    fn shift_source(&self, x: usize, y: usize) -> Wrapping<u8> {
+        if self.quirks.shift_uses_vy {
+            self.v[y]
+        } else {
+            self.v[x]
+        }
     }
 
-    fn shl(&mut self, op: u16) {
-        let x = get_x(op);
-        self.v[0xF] = (self.v[x] & Wrapping(0x80)) >> 7;
-        self.v[x] <<= 1;
+    fn reset_vf(&mut self) {
+        if self.quirks.logic_resets_vf {
+            self.v[0xF] = Wrapping(0);
+        }
     }
 
-    fn rand(&mut self, op: u16) {
-        self.v[get_x(op)] = Wrapping(self.rng.gen::<u8>() & get_byte(op));
+    fn rand(&mut self, x: usize, byte: u8) {
+        let value: u8 = match &mut self.rng {
+            RngSource::Seeded { rng, .. } => rng.gen(),
+            RngSource::Injected(rng) => rng.gen(),
+        };
+        self.v[x] = Wrapping(value & byte);
     }
 
-    fn draw(&mut self, op: u16) {
-        let length = get_nibble(op) as u8;
-        let i = self.i as usize;
-        self.v[0xF] = self.display_data.draw_sprite(
-            self.v[get_x(op)].0,
-            self.v[get_y(op)].0,
-            &self.ram[i..i + length as usize],
-        );
+    fn draw(&mut self, x: usize, y: usize, rows: u8) -> Result<(), VmError> {
+        let large = rows == 0 && self.platform.supports_schip();
+        let rows = if large { 32 } else { rows as usize };
+        let length = rows * self.display_data.selected_plane_count();
+        let mut sprite = Vec::with_capacity(length);
+        for count in 0..length {
+            let address = self.i_address(count)?;
+            sprite.push(self.read_ram(address));
+        }
+        let (x, y) = (self.v[x].0, self.v[y].0);
+        self.v[0xF] = if large {
+            self.display_data
+                .draw_large_sprite(x, y, &sprite, self.quirks.clip_sprites)
+        } else {
+            self.display_data
+                .draw_sprite(x, y, &sprite, self.quirks.clip_sprites)
+        };
         self.draw_flag = true;
+        Ok(())
+    }
+
+    fn sprite_addr(&mut self, x: usize) {
+        self.i = 0x50 + 5 * self.v[x].0 as u16;
+    }
+
+    fn select_planes(&mut self, planes: u8) {
+        self.display_data.select_planes(planes);
+    }
+
+    fn load_audio_pattern(&mut self) -> Result<(), VmError> {
+        for count in 0..AUDIO_PATTERN_SIZE {
+            let address = self.i_address(count)?;
+            self.audio_pattern[count] = self.read_ram(address);
+        }
+        self.audio_flag = true;
+        Ok(())
+    }
+
+    fn large_sprite_addr(&mut self, x: usize) {
+        self.i = LARGE_FONT_START + 10 * (self.v[x].0 & 0xF) as u16;
+    }
+
+    fn bcd(&mut self, x: usize) -> Result<(), VmError> {
+        let addresses = [self.i_address(0)?, self.i_address(1)?, self.i_address(2)?];
+        let vx = self.v[x];
+        self.write_ram(addresses[0], (vx / Wrapping(100)).0);
+        self.write_ram(addresses[1], (vx / Wrapping(10)).0 % 10);
+        self.write_ram(addresses[2], vx.0 % 10);
+        Ok(())
+    }
+
+    fn store(&mut self, x: usize) -> Result<(), VmError> {
+        for count in 0..=x {
+            let address = self.i_address(count)?;
+            self.write_ram(address, self.v[count].0);
+        }
+        self.increment_i(x);
+        Ok(())
+    }
+
+    fn read(&mut self, x: usize) -> Result<(), VmError> {
+        for count in 0..=x {
+            let address = self.i_address(count)?;
+            self.v[count] = Wrapping(self.read_ram(address));
+        }
+        self.increment_i(x);
+        Ok(())
+    }
+
+    fn increment_i(&mut self, x: usize) {
+        if self.quirks.load_store_increments_i {
+            self.i = self.i.wrapping_add(x as u16 + 1);
+        }
+    }
+
+    fn save_range(&mut self, x: usize, y: usize) -> Result<(), VmError> {
+        for (offset, register) in register_range(x, y).enumerate() {
+            let address = self.i_address(offset)?;
+            self.write_ram(address, self.v[register].0);
+        }
+        Ok(())
     }
 
-    fn sprite_addr(&mut self, op: u16) {
-        self.i = 0x50 + 5 * self.v[get_x(op)].0 as u16;
+    fn load_range(&mut self, x: usize, y: usize) -> Result<(), VmError> {
+        for (offset, register) in register_range(x, y).enumerate() {
+            let address = self.i_address(offset)?;
+            self.v[register] = Wrapping(self.read_ram(address));
+        }
+        Ok(())
+    }
+
+    fn save_flags(&mut self, x: usize) -> Result<(), VmError> {
+        let count = self.rpl_count(x);
+        for flag in 0..count {
+            self.rpl[flag] = self.v[flag].0;
+        }
+        if let Some(path) = &self.rpl_path {
+            fs::write(path, &self.rpl[..self.platform.rpl_flags()])?;
+        }
+        Ok(())
     }
 
-    fn bcd(&mut self, op: u16) {
-        let i = self.i as usize;
-        let vx = self.v[get_x(op)];
-        self.ram[i] = (vx / Wrapping(100)).0;
-        self.ram[i + 1] = (vx / Wrapping(10)).0 % 10;
-        self.ram[i + 2] = vx.0 % 10;
+    fn load_flags(&mut self, x: usize) {
+        for flag in 0..self.rpl_count(x) {
+            self.v[flag] = Wrapping(self.rpl[flag]);
+        }
+    }
+
+    fn rpl_count(&self, x: usize) -> usize {
+        (x + 1).min(self.platform.rpl_flags())
     }
 
-    fn store(&mut self, op: u16) {
-        for count in 0..=get_x(op) {
-            self.ram[self.i as usize + count] = self.v[count].0;
+    fn wait_for_keypress(&mut self, x: usize) {
+        match (0..16).find(|key| self.keys[*key].0) {
+            Some(key) => self.v[x] = Wrapping(key as u8),
+            None => self.jump_flag = true,
         }
     }
+}
 
-    fn read(&mut self, op: u16) {
-        for count in 0..=get_x(op) {
-            self.v[count] = Wrapping(self.ram[self.i as usize + count]);
+enum RngSource {
+    Seeded { seed: u64, rng: Box<ChaCha20Rng> },
+    Injected(Box<dyn RngCore>),
+}
+
+impl RngSource {
+    fn seeded(seed: u64, position: u128) -> Self {
+        let mut rng = ChaCha20Rng::seed_from_u64(seed);
+        // Seeking also generates the first block, which get_word_pos needs.
+        rng.set_word_pos(position);
+        RngSource::Seeded {
+            seed,
+            rng: Box::new(rng),
         }
     }
+}
 
-    fn wait_for_keypress(&mut self, op: u16) {
-        self.v[get_x(op)] = Wrapping(self.frontend.wait_for_keypress());
+fn register_range(x: usize, y: usize) -> Box<dyn Iterator<Item = usize>> {
+    if x <= y {
+        Box::new(x..=y)
+    } else {
+        Box::new((y..=x).rev())
     }
 }
 
@@ -0,0 +1,102 @@
+use super::memory::display::{HIRES_CHIP8_HEIGHT, LORES_HEIGHT, LORES_WIDTH};
+use super::Quirks;
+
+const PROGRAM_START: u16 = 0x200;
+const HIRES_JUMP: [u8; 2] = [0x12, 0x60];
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Platform {
+    #[default]
+    Chip8,
+    HiresChip8,
+    SuperChip,
+    XoChip,
+}
+
+impl Platform {
+    pub const NAMES: [&'static str; 4] = ["chip8", "hires", "schip", "xochip"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "chip8" => Some(Platform::Chip8),
+            "hires" => Some(Platform::HiresChip8),
+            "schip" => Some(Platform::SuperChip),
+            "xochip" => Some(Platform::XoChip),
+            _ => None,
+        }
+    }
+
+    pub fn id(self) -> u8 {
+        match self {
+            Platform::Chip8 => 0,
+            Platform::HiresChip8 => 1,
+            Platform::SuperChip => 2,
+            Platform::XoChip => 3,
+        }
+    }
+
+    pub fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(Platform::Chip8),
+            1 => Some(Platform::HiresChip8),
+            2 => Some(Platform::SuperChip),
+            3 => Some(Platform::XoChip),
+            _ => None,
+        }
+    }
+
+    pub fn detect(rom: &[u8]) -> Self {
+        if rom.starts_with(&HIRES_JUMP) {
+            Platform::HiresChip8
+        } else {
+            Platform::Chip8
+        }
+    }
+
+    pub fn default_quirks(self) -> Quirks {
+        match self {
+            Platform::Chip8 => Quirks::legacy(),
+            Platform::HiresChip8 => Quirks::cosmac_vip(),
+            Platform::SuperChip => Quirks::schip(),
+            Platform::XoChip => Quirks::modern(),
+        }
+    }
+
+    pub fn program_start(self) -> u16 {
+        PROGRAM_START
+    }
+
+    pub fn display_size(self) -> (usize, usize) {
+        match self {
+            Platform::HiresChip8 => (LORES_WIDTH, HIRES_CHIP8_HEIGHT),
+            _ => (LORES_WIDTH, LORES_HEIGHT),
+        }
+    }
+
+    pub fn memory_size(self) -> usize {
+        match self {
+            Platform::XoChip => 0x10000,
+            _ => 0x1000,
+        }
+    }
+
+    pub fn supports_schip(self) -> bool {
+        self == Platform::SuperChip || self == Platform::XoChip
+    }
+
+    pub fn supports_hires_chip8(self) -> bool {
+        self == Platform::HiresChip8
+    }
+
+    pub fn supports_xochip(self) -> bool {
+        self == Platform::XoChip
+    }
+
+    pub fn rpl_flags(self) -> usize {
+        match self {
+            Platform::Chip8 | Platform::HiresChip8 => 0,
+            Platform::SuperChip => 8,
+            Platform::XoChip => 16,
+        }
+    }
+}
@@ -0,0 +1,97 @@
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Quirks {
+    pub shift_uses_vy: bool,
+    pub load_store_increments_i: bool,
+    pub logic_resets_vf: bool,
+    pub jump_uses_vx: bool,
+    pub clip_sprites: bool,
+}
+
+impl Quirks {
+    pub const PROFILES: [&'static str; 5] = ["legacy", "vip", "chip48", "schip", "modern"];
+
+    pub fn legacy() -> Self {
+        Self {
+            shift_uses_vy: false,
+            load_store_increments_i: false,
+            logic_resets_vf: false,
+            jump_uses_vx: false,
+            clip_sprites: false,
+        }
+    }
+
+    pub fn cosmac_vip() -> Self {
+        Self {
+            shift_uses_vy: true,
+            load_store_increments_i: true,
+            logic_resets_vf: true,
+            jump_uses_vx: false,
+            clip_sprites: true,
+        }
+    }
+
+    pub fn chip48() -> Self {
+        Self {
+            shift_uses_vy: false,
+            load_store_increments_i: true,
+            logic_resets_vf: false,
+            jump_uses_vx: true,
+            clip_sprites: true,
+        }
+    }
+
+    pub fn schip() -> Self {
+        Self {
+            shift_uses_vy: false,
+            load_store_increments_i: false,
+            logic_resets_vf: false,
+            jump_uses_vx: true,
+            clip_sprites: true,
+        }
+    }
+
+    pub fn modern() -> Self {
+        Self {
+            shift_uses_vy: true,
+            load_store_increments_i: true,
+            logic_resets_vf: false,
+            jump_uses_vx: false,
+            clip_sprites: false,
+        }
+    }
+
+    pub fn from_profile(name: &str) -> Option<Self> {
+        match name {
+            "legacy" => Some(Self::legacy()),
+            "vip" => Some(Self::cosmac_vip()),
+            "chip48" => Some(Self::chip48()),
+            "schip" => Some(Self::schip()),
+            "modern" => Some(Self::modern()),
+            _ => None,
+        }
+    }
+
+    pub fn to_bits(self) -> u8 {
+        self.shift_uses_vy as u8
+            | (self.load_store_increments_i as u8) << 1
+            | (self.logic_resets_vf as u8) << 2
+            | (self.jump_uses_vx as u8) << 3
+            | (self.clip_sprites as u8) << 4
+    }
+
+    pub fn from_bits(bits: u8) -> Self {
+        Self {
+            shift_uses_vy: bits & 1 != 0,
+            load_store_increments_i: bits & 1 << 1 != 0,
+            logic_resets_vf: bits & 1 << 2 != 0,
+            jump_uses_vx: bits & 1 << 3 != 0,
+            clip_sprites: bits & 1 << 4 != 0,
+        }
+    }
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Self::legacy()
+    }
+}
@@ -0,0 +1,135 @@
+use std::collections::VecDeque;
+
+pub const DEFAULT_REWIND_FRAMES: usize = 600;
+
+struct Delta {
+    length: usize,
+    runs: Vec<u8>,
+}
+
+pub struct RewindBuffer {
+    capacity: usize,
+    current: Option<Vec<u8>>,
+    deltas: VecDeque<Delta>,
+}
+
+impl RewindBuffer {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            current: None,
+            deltas: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn len(&self) -> usize {
+        self.deltas.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.deltas.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.current = None;
+        self.deltas.clear();
+    }
+
+    pub fn push(&mut self, state: Vec<u8>) {
+        if self.capacity == 0 {
+            return;
+        }
+        if let Some(previous) = self.current.take() {
+            self.deltas.push_back(encode(&state, &previous));
+            if self.deltas.len() > self.capacity {
+                self.deltas.pop_front();
+            }
+        }
+        self.current = Some(state);
+    }
+
+    pub fn rewind(&mut self, frames: usize) -> Option<(usize, &[u8])> {
+        let mut rewound = 0;
+        while rewound < frames {
+            let (delta, current) = match (self.deltas.pop_back(), &mut self.current) {
+                (Some(delta), Some(current)) => (delta, current),
+                _ => break,
+            };
+            apply(current, &delta);
+            rewound += 1;
+        }
+        match &self.current {
+            Some(current) if rewound > 0 => Some((rewound, current)),
+            _ => None,
+        }
+    }
+}
+
+fn encode(from: &[u8], to: &[u8]) -> Delta {
+    let length = from.len().max(to.len());
+    let xor: Vec<u8> = (0..length)
+        .map(|i| from.get(i).unwrap_or(&0) ^ to.get(i).unwrap_or(&0))
+        .collect();
+
+    let mut runs = Vec::new();
+    let mut i = 0;
+    while i < length {
+        let zeros = xor[i..].iter().take_while(|byte| **byte == 0).count();
+        i += zeros;
+        if i == length {
+            break;
+        }
+        let literals = xor[i..].iter().take_while(|byte| **byte != 0).count();
+        write_varint(&mut runs, zeros);
+        write_varint(&mut runs, literals);
+        runs.extend_from_slice(&xor[i..i + literals]);
+        i += literals;
+    }
+
+    Delta {
+        length: to.len(),
+        runs,
+    }
+}
+
+fn apply(state: &mut Vec<u8>, delta: &Delta) {
+    state.resize(state.len().max(delta.length), 0);
+    let mut runs = &delta.runs[..];
+    let mut i = 0;
+    while !runs.is_empty() {
+        i += read_varint(&mut runs);
+        let literals = read_varint(&mut runs);
+        for (byte, xor) in state[i..i + literals].iter_mut().zip(&runs[..literals]) {
+            *byte ^= xor;
+        }
+        runs = &runs[literals..];
+        i += literals;
+    }
+    state.truncate(delta.length);
+}
+
+fn write_varint(data: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        data.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    data.push(value as u8);
+}
+
+fn read_varint(data: &mut &[u8]) -> usize {
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+        let byte = data[0];
+        *data = &data[1..];
+        value |= ((byte & 0x7F) as usize) << shift;
+        if byte & 0x80 == 0 {
+            return value;
+        }
+        shift += 7;
+    }
+}
@@ -0,0 +1,315 @@
+use super::memory::display::{HIRES_HEIGHT, HIRES_WIDTH, PLANES};
+use super::memory::{AddressPolicy, DisplayBuffer, Ram};
+use super::{Chip8Vm, Platform, Quirks, VmError};
+use super::{AUDIO_PATTERN_SIZE, MAX_RPL_FLAGS, MAX_STACK_DEPTH};
+use crate::frontend::Keys;
+
+use std::convert::TryInto;
+use std::num::Wrapping;
+
+const MAGIC: &[u8; 4] = b"YACI";
+pub const STATE_VERSION: u16 = 1;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StateHeader {
+    pub version: u16,
+    pub platform: Platform,
+    pub quirks: Quirks,
+    pub rom_hash: u64,
+}
+
+impl StateHeader {
+    pub fn parse(state: &[u8]) -> Result<Self, VmError> {
+        StateReader::new(state).header()
+    }
+}
+
+impl Chip8Vm {
+    pub fn rom_hash(&self) -> u64 {
+        self.rom_hash
+    }
+
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut state = StateWriter::default();
+        state.bytes(MAGIC);
+        state.u16(STATE_VERSION);
+        state.u8(self.platform.id());
+        state.u8(self.quirks.to_bits());
+        state.u64(self.rom_hash);
+
+        state.bytes(&self.v.iter().map(|v| v.0).collect::<Vec<_>>());
+        state.u16(self.i);
+        state.u16(self.pc);
+        state.u8(self.sp as u8);
+        state.u8(self.stack_depth as u8);
+        self.stack.iter().for_each(|address| state.u16(*address));
+        state.u8(self.dt);
+        state.u8(self.st);
+        state.u8(self.buzzer as u8);
+        state.u8(self.exited as u8);
+        state.u16(self.keys.to_bits());
+        state.u8(self.seed().is_some() as u8);
+        state.u64(self.seed().unwrap_or_default());
+        state.u128(self.rng_position());
+        state.bytes(&self.rpl);
+        state.bytes(&self.audio_pattern);
+        state.u8(self.pitch);
+
+        state.u8(policy_id(self.ram.policy()));
+        state.u32(self.ram.size() as u32);
+        state.bytes(&self.ram[..]);
+        state.u16(self.display_data.width() as u16);
+        state.u16(self.display_data.height() as u16);
+        state.u8(self.display_data.planes());
+        state.bytes(&self.display_data.data);
+
+        state.data
+    }
+
+    pub fn load_state(&mut self, state: &[u8]) -> Result<(), VmError> {
+        let mut reader = StateReader::new(state);
+        let header = reader.header()?;
+        if header.platform != self.platform {
+            return Err(VmError::InvalidState(format!(
+                "state was saved for a different platform ({:?})",
+                header.platform
+            )));
+        }
+        if header.rom_hash != self.rom_hash {
+            return Err(VmError::InvalidState(format!(
+                "state was saved for a different rom (hash {:016X})",
+                header.rom_hash
+            )));
+        }
+
+        let mut v = [Wrapping(0); 16];
+        for (register, value) in v.iter_mut().zip(reader.bytes(16)?) {
+            *register = Wrapping(*value);
+        }
+        let i = reader.u16()?;
+        let pc = reader.u16()?;
+        let sp = reader.u8()? as usize;
+        let stack_depth = reader.u8()? as usize;
+        if sp > MAX_STACK_DEPTH || stack_depth > MAX_STACK_DEPTH {
+            return Err(VmError::InvalidState(
+                "stack pointer out of range".to_owned(),
+            ));
+        }
+        let mut stack = [0; MAX_STACK_DEPTH];
+        for address in stack.iter_mut() {
+            *address = reader.u16()?;
+        }
+        let dt = reader.u8()?;
+        let st = reader.u8()?;
+        let buzzer = reader.u8()? != 0;
+        let exited = reader.u8()? != 0;
+        let keys = Keys::from_bits(reader.u16()?);
+        let seeded = reader.u8()? != 0;
+        let seed = reader.u64()?;
+        let rng_position = reader.u128()?;
+        let rpl: [u8; MAX_RPL_FLAGS] = reader.array()?;
+        let audio_pattern: [u8; AUDIO_PATTERN_SIZE] = reader.array()?;
+        let pitch = reader.u8()?;
+
+        let policy = policy_from_id(reader.u8()?)?;
+        let ram_size = reader.u32()? as usize;
+        if ram_size != self.ram.size() {
+            return Err(VmError::InvalidState(format!(
+                "memory size {} does not match the platform",
+                ram_size
+            )));
+        }
+        let mut ram = Ram::new(ram_size);
+        ram[..].copy_from_slice(reader.bytes(ram_size)?);
+        ram.set_policy(policy);
+        let width = reader.u16()? as usize;
+        let height = reader.u16()? as usize;
+        let planes = reader.u8()?;
+        let hires =
+            self.platform.supports_schip() && (width, height) == (HIRES_WIDTH, HIRES_HEIGHT);
+        if (width, height) != self.platform.display_size() && !hires {
+            return Err(VmError::InvalidState(format!(
+                "display size {}x{} does not match the platform",
+                width, height
+            )));
+        }
+        let valid_planes = if self.platform.supports_xochip() {
+            planes < 1 << PLANES
+        } else {
+            planes == 1
+        };
+        if !valid_planes {
+            return Err(VmError::InvalidState(format!(
+                "plane mask {} does not match the platform",
+                planes
+            )));
+        }
+        let mut display = DisplayBuffer::new(width, height);
+        display.select_planes(planes);
+        display.data.copy_from_slice(reader.bytes(width * height)?);
+        if !reader.is_empty() {
+            return Err(VmError::InvalidState("trailing data".to_owned()));
+        }
+
+        self.quirks = header.quirks;
+        self.v = v;
+        self.i = i;
+        self.pc = pc;
+        self.sp = sp;
+        self.stack_depth = stack_depth;
+        self.stack = stack;
+        self.dt = dt;
+        self.st = st;
+        self.exited = exited;
+        self.keys = keys;
+        if seeded {
+            self.restore_rng(seed, rng_position);
+        }
+        self.rpl = rpl;
+        if self.audio_pattern != audio_pattern || self.pitch != pitch {
+            self.audio_pattern = audio_pattern;
+            self.pitch = pitch;
+            self.audio_flag = true;
+        }
+        self.ram = ram;
+        self.display_data = display;
+        self.jump_flag = false;
+
+        if self.buzzer != buzzer {
+            self.buzzer = buzzer;
+            self.frontend.set_buzzer(buzzer);
+        }
+        self.frontend.draw(&self.display_data);
+
+        Ok(())
+    }
+}
+
+pub(crate) fn hash_rom(rom: &[u8]) -> u64 {
+    rom.iter().fold(0xCBF2_9CE4_8422_2325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x0100_0000_01B3)
+    })
+}
+
+fn policy_id(policy: AddressPolicy) -> u8 {
+    match policy {
+        AddressPolicy::Wrap => 0,
+        AddressPolicy::Fault => 1,
+        AddressPolicy::Clamp => 2,
+    }
+}
+
+fn policy_from_id(id: u8) -> Result<AddressPolicy, VmError> {
+    match id {
+        0 => Ok(AddressPolicy::Wrap),
+        1 => Ok(AddressPolicy::Fault),
+        2 => Ok(AddressPolicy::Clamp),
+        _ => Err(VmError::InvalidState(format!(
+            "unknown address policy {}",
+            id
+        ))),
+    }
+}
+
+#[derive(Default)]
+struct StateWriter {
+    data: Vec<u8>,
+}
+
+impl StateWriter {
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.data.extend_from_slice(bytes);
+    }
+
+    fn u8(&mut self, value: u8) {
+        self.data.push(value);
+    }
+
+    fn u16(&mut self, value: u16) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    fn u64(&mut self, value: u64) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    fn u128(&mut self, value: u128) {
+        self.bytes(&value.to_le_bytes());
+    }
+}
+
+struct StateReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> StateReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    fn header(&mut self) -> Result<StateHeader, VmError> {
+        if self.bytes(MAGIC.len())? != MAGIC {
+            return Err(VmError::InvalidState("not a yaci save state".to_owned()));
+        }
+        let version = self.u16()?;
+        if version != STATE_VERSION {
+            return Err(VmError::InvalidState(format!(
+                "unsupported version {}",
+                version
+            )));
+        }
+        Ok(StateHeader {
+            version,
+            platform: self.platform()?,
+            quirks: Quirks::from_bits(self.u8()?),
+            rom_hash: self.u64()?,
+        })
+    }
+
+    fn platform(&mut self) -> Result<Platform, VmError> {
+        let id = self.u8()?;
+        Platform::from_id(id)
+            .ok_or_else(|| VmError::InvalidState(format!("unknown platform {}", id)))
+    }
+
+    fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    fn bytes(&mut self, length: usize) -> Result<&'a [u8], VmError> {
+        if self.data.len() < length {
+            return Err(VmError::InvalidState("unexpected end of data".to_owned()));
+        }
+        let (bytes, rest) = self.data.split_at(length);
+        self.data = rest;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], VmError> {
+        Ok(self.bytes(N)?.try_into().unwrap())
+    }
+
+    fn u8(&mut self) -> Result<u8, VmError> {
+        Ok(self.array::<1>()?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, VmError> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+
+    fn u32(&mut self) -> Result<u32, VmError> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    fn u64(&mut self) -> Result<u64, VmError> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+
+    fn u128(&mut self) -> Result<u128, VmError> {
+        Ok(u128::from_le_bytes(self.array()?))
+    }
+}
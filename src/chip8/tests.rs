@@ -1,5 +1,18 @@
-use crate::chip8::Chip8Vm;
-use crate::frontend::MockFrontEnd;
+use crate::chip8::memory::display::{LORES_HEIGHT, LORES_WIDTH};
+use crate::chip8::memory::AddressPolicy;
+use crate::chip8::memory::DisplayBuffer;
+use crate::chip8::{
+    Chip8Vm, DecodeError, Fetched, Instruction, Platform, Quirks, StateHeader, Tracer, VmError,
+    AUDIO_PATTERN_SIZE,
+};
+use crate::frontend::{FrontEnd, HeadlessFrontEnd, InvalidKey, Key, Keys, MockFrontEnd};
+
+use rand::rngs::mock::StepRng;
+use std::cell::{Cell, RefCell};
+use std::fs;
+use std::io::{self, Write};
+use std::num::Wrapping;
+use std::rc::Rc;
 
 #[test]
 fn test_jump() {
@@ -32,7 +45,7 @@ fn test_call() {
     assert_ne!(vm.pc, 0xFF0, "{}", init_pc());
     assert_ne!(vm.stack[1], 0x200, "{}", init_stack());
 
-    vm.call(0xFF0);
+    vm.call(0xFF0).unwrap();
 
     assert_eq!(vm.pc, 0xFF0, "{}", ch_pc());
     assert_eq!(vm.stack[1], 0x200, "{}", ch_stack());
@@ -48,23 +61,51 @@ fn test_return() {
     assert_ne!(vm.sp, 0, "{}", init_sp());
     assert_ne!(vm.stack[0], 0, "{}", init_stack());
 
-    vm.ret(0);
+    vm.ret().unwrap();
 
     assert_eq!(vm.pc, 0xFFF, "{}", ch_pc());
     assert_eq!(vm.sp, 0, "{}", ch_sp());
     assert_eq!(vm.stack[0], 0, "{}", ch_stack());
 }
 
+#[test]
+fn test_stack_overflow() {
+    let mut vm = init_vm();
+
+    vm.set_stack_depth(2);
+    vm.call(0x300).unwrap();
+
+    match vm.call(0x400) {
+        Err(VmError::StackOverflow { pc, stack }) => {
+            assert_eq!(pc, 0x300);
+            assert_eq!(stack, vec![0xFFF, 0x200]);
+        }
+        _ => panic!("Expected a stack overflow."),
+    }
+}
+
+#[test]
+fn test_stack_underflow() {
+    let mut vm = init_vm();
+
+    vm.ret().unwrap();
+
+    match vm.ret() {
+        Err(VmError::StackUnderflow { stack, .. }) => assert!(stack.is_empty()),
+        _ => panic!("Expected a stack underflow."),
+    }
+}
+
 #[test]
 fn test_b_eq() {
     let mut vm = init_vm();
 
-    vm.skip_b_eq(0x0EF);
+    vm.skip_b_eq(0x0, 0xEF).unwrap();
 
     assert_eq!(vm.pc, 0x200, "{}", init_pc());
 
     let prev_val = vm.pc;
-    vm.skip_b_eq(0x0FF);
+    vm.skip_b_eq(0x0, 0xFF).unwrap();
 
     assert_eq!(vm.pc, 0x202, "{}", ch_pc());
     assert_eq!(vm.pc, prev_val + 2);
@@ -74,12 +115,12 @@ fn test_b_eq() {
 fn test_b_ne() {
     let mut vm = init_vm();
 
-    vm.skip_b_ne(0x0FF);
+    vm.skip_b_ne(0x0, 0xFF).unwrap();
 
     assert_eq!(vm.pc, 0x200, "{}", init_pc());
 
     let prev_val = vm.pc;
-    vm.skip_b_ne(0x0EF);
+    vm.skip_b_ne(0x0, 0xEF).unwrap();
 
     assert_eq!(vm.pc, 0x202, "{}", ch_pc());
     assert_eq!(vm.pc, prev_val + 2);
@@ -89,12 +130,12 @@ fn test_b_ne() {
 fn test_y_eq() {
     let mut vm = init_vm();
 
-    vm.skip_y_eq(0x020);
+    vm.skip_y_eq(0x0, 0x2).unwrap();
 
     assert_eq!(vm.pc, 0x200, "{}", init_pc());
 
     let prev_val = vm.pc;
-    vm.skip_y_eq(0x0E0);
+    vm.skip_y_eq(0x0, 0xE).unwrap();
 
     assert_eq!(vm.pc, 0x202, "{}", ch_pc());
     assert_eq!(vm.pc, prev_val + 2);
@@ -104,12 +145,12 @@ fn test_y_eq() {
 fn test_y_ne() {
     let mut vm = init_vm();
 
-    vm.skip_y_ne(0x0E0);
+    vm.skip_y_ne(0x0, 0xE).unwrap();
 
     assert_eq!(vm.pc, 0x200, "{}", init_pc());
 
     let prev_val = vm.pc;
-    vm.skip_y_ne(0x020);
+    vm.skip_y_ne(0x0, 0x2).unwrap();
 
     assert_eq!(vm.pc, 0x202, "{}", ch_pc());
     assert_eq!(vm.pc, prev_val + 2);
@@ -121,7 +162,7 @@ fn test_put_x_b() {
 
     assert_ne!(vm.v[0xE].0, 0x20);
 
-    vm.put_x_b(0xE20);
+    vm.put_x_b(0xE, 0x20);
 
     assert_eq!(vm.v[0xE].0, 0x20);
 }
@@ -132,7 +173,7 @@ fn test_put_x_y() {
 
     assert_ne!(vm.v[0].0, 0x20);
 
-    vm.put_x_y(0x010);
+    vm.put_x_y(0x0, 0x1);
 
     assert_eq!(vm.v[0].0, 0x20);
     assert_eq!(vm.v[0].0, vm.v[1].0);
@@ -145,7 +186,7 @@ fn test_add_x_b() {
     assert_ne!(vm.v[1].0, 0x2E);
 
     let prev_val = vm.v[1].0;
-    vm.add_x_b(0x10E);
+    vm.add_x_b(0x1, 0x0E);
 
     assert_eq!(vm.v[1].0, 0x2E);
     assert_eq!(prev_val, vm.v[1].0 - 0xE);
@@ -158,7 +199,7 @@ fn test_add_x_y() {
     assert_ne!(vm.v[1].0, 0x2E);
 
     let prev_val = vm.v[1];
-    vm.add_x_y(0x124);
+    vm.add_x_y(0x1, 0x2);
 
     assert_eq!(vm.v[0xF].0, 0);
     assert_eq!(vm.v[1].0, 0x2E);
@@ -169,7 +210,7 @@ fn test_add_x_y() {
     assert_ne!(vm.v[0].0, 0xE);
 
     let prev_val = vm.v[0];
-    vm.add_x_y(0x024);
+    vm.add_x_y(0x0, 0x2);
 
     assert_eq!(vm.v[0xF].0, 1);
     assert_eq!(vm.v[0].0, 0xD);
@@ -183,7 +224,7 @@ fn test_or() {
     assert_ne!(vm.v[1].0, 0x2E);
 
     let prev_val = vm.v[1].0;
-    vm.or(0x120);
+    vm.or(0x1, 0x2);
 
     assert_eq!(vm.v[1].0, 0x2E);
     assert_eq!(vm.v[1].0, prev_val | vm.v[2].0);
@@ -196,7 +237,7 @@ fn test_and() {
     assert_ne!(vm.v[1].0, 0x2E);
 
     let prev_val = vm.v[1].0;
-    vm.and(0x120);
+    vm.and(0x1, 0x2);
 
     assert_eq!(vm.v[1].0, 0);
     assert_eq!(vm.v[1].0, prev_val & vm.v[2].0);
@@ -209,7 +250,7 @@ fn test_xor() {
     assert_ne!(vm.v[1].0, 0x2E);
 
     let prev_val = vm.v[1].0;
-    vm.xor(0x120);
+    vm.xor(0x1, 0x2);
 
     assert_eq!(vm.v[1].0, 0x2E);
     assert_eq!(vm.v[1].0, prev_val ^ vm.v[2].0);
@@ -220,7 +261,7 @@ fn test_sub() {
     let mut vm = init_vm();
 
     let prev_val = vm.v[0];
-    vm.sub(0x015);
+    vm.sub(0x0, 0x1);
 
     assert_eq!(vm.v[0xF].0, 1);
     assert_eq!(vm.v[0].0, 0xDF);
@@ -231,7 +272,7 @@ fn test_sub() {
     assert_ne!(vm.v[2].0, 0xEE);
 
     let prev_val = vm.v[2];
-    vm.sub(0x215);
+    vm.sub(0x2, 0x1);
 
     assert_eq!(vm.v[0xF].0, 0);
     assert_eq!(vm.v[2].0, 0xEE);
@@ -243,7 +284,7 @@ fn test_subn() {
     let mut vm = init_vm();
 
     let prev_val = vm.v[1];
-    vm.subn(0x105);
+    vm.subn(0x1, 0x0);
 
     assert_eq!(vm.v[0xF].0, 1);
     assert_eq!(vm.v[1].0, 0xDF);
@@ -254,7 +295,7 @@ fn test_subn() {
     assert_ne!(vm.v[2].0, 0xEE);
 
     let prev_val = vm.v[1];
-    vm.subn(0x125);
+    vm.subn(0x1, 0x2);
 
     assert_eq!(vm.v[0xF].0, 0);
     assert_eq!(vm.v[1].0, 0xEE);
@@ -266,7 +307,7 @@ fn test_shr() {
     let mut vm = init_vm();
 
     let prev_val = vm.v[2];
-    vm.shr(0x206);
+    vm.shr(0x2, 0x0);
 
     assert_eq!(vm.v[0xF].0, 0);
     assert_eq!(vm.v[2].0, 0x7);
@@ -276,7 +317,7 @@ fn test_shr() {
 
     let prev_val = vm.v[0];
 
-    vm.shr(0x006);
+    vm.shr(0x0, 0x0);
 
     assert_eq!(vm.v[0xF].0, 1);
     assert_eq!(vm.v[0].0, 0x7F);
@@ -288,7 +329,7 @@ fn test_shl() {
     let mut vm = init_vm();
 
     let prev_val = vm.v[2];
-    vm.shl(0x20E);
+    vm.shl(0x2, 0x0);
 
     assert_eq!(vm.v[0xF].0, 0);
     assert_eq!(vm.v[2].0, 0x1C);
@@ -298,13 +339,491 @@ fn test_shl() {
 
     let prev_val = vm.v[0];
 
-    vm.shl(0x00E);
+    vm.shl(0x0, 0x0);
 
     assert_eq!(vm.v[0xF].0, 1);
     assert_eq!(vm.v[0].0, 0xFE);
     assert_eq!(vm.v[0], prev_val << 1);
 }
 
+#[test]
+fn test_vf_destination() {
+    let mut vm = init_vm();
+    vm.v[0xF] = Wrapping(0xF0);
+    vm.add_x_y(0xF, 0x1);
+    assert_eq!(vm.v[0xF].0, 0x21);
+
+    let mut vm = init_vm();
+    vm.v[0xF] = Wrapping(0xF0);
+    vm.sub(0xF, 0x1);
+    assert_eq!(vm.v[0xF].0, 0xE1);
+
+    let mut vm = init_vm();
+    vm.v[0xF] = Wrapping(0xF0);
+    vm.subn(0xF, 0x1);
+    assert_eq!(vm.v[0xF].0, 0x20);
+
+    let mut vm = init_vm();
+    vm.v[0xF] = Wrapping(0xF1);
+    vm.shr(0xF, 0x0);
+    assert_eq!(vm.v[0xF].0, 0);
+
+    let mut vm = init_vm();
+    vm.v[0xF] = Wrapping(0xF1);
+    vm.shl(0xF, 0x0);
+    assert_eq!(vm.v[0xF].0, 2);
+}
+
+#[test]
+fn test_shift_quirk() {
+    let mut vm = init_vm_with(Quirks::cosmac_vip());
+
+    vm.shr(0x2, 0x0);
+
+    assert_eq!(vm.v[2].0, 0x7F);
+    assert_eq!(vm.v[0xF].0, 1);
+
+    vm.shl(0x2, 0x1);
+
+    assert_eq!(vm.v[2].0, 0x40);
+    assert_eq!(vm.v[0xF].0, 0);
+}
+
+#[test]
+fn test_default_quirks() {
+    let mut vm = Chip8Vm::new(Box::new(MockFrontEnd::default()), Quirks::default());
+    vm.load_rom_bytes(&[
+        0x60, 0x81, 0x61, 0x03, 0x80, 0x16, 0xA3, 0x00, 0xF1, 0x55, 0x62, 0x3F, 0x63, 0x00, 0xD2,
+        0x31, 0x6F, 0x07, 0x80, 0x11, 0xB2, 0x10,
+    ])
+    .unwrap();
+    for _ in 0..11 {
+        vm.step().unwrap();
+    }
+
+    assert_eq!(Quirks::default(), Quirks::legacy());
+    assert_eq!(Platform::Chip8.default_quirks(), Quirks::default());
+    assert_eq!(vm.v[0].0, 0x43);
+    assert_eq!(vm.v[0xF].0, 7);
+    assert_eq!(vm.i, 0x300);
+    assert_eq!(vm.ram[0x300], 0x40);
+    assert_eq!(vm.display_data.data[0], 1);
+    assert_eq!(vm.pc, 0x253);
+}
+
+#[test]
+fn test_logic_quirk() {
+    let mut vm = init_vm_with(Quirks::cosmac_vip());
+
+    vm.v[0xF] = Wrapping(1);
+    vm.or(0x1, 0x2);
+
+    assert_eq!(vm.v[0xF].0, 0);
+}
+
+#[test]
+fn test_jump_quirk() {
+    let mut vm = init_vm_with(Quirks::schip());
+
+    vm.jump_0(0x210);
+
+    assert_eq!(vm.pc, 0x21E);
+}
+
+#[test]
+fn test_load_store_quirk() {
+    let mut vm = init_vm_with(Quirks::cosmac_vip());
+
+    vm.i = 0x300;
+    vm.store(0x2).unwrap();
+
+    assert_eq!(vm.i, 0x303);
+    assert_eq!(vm.ram[0x302], 0xE);
+
+    vm.i = 0x300;
+    vm.v[0] = Wrapping(0);
+    vm.read(0x0).unwrap();
+
+    assert_eq!(vm.i, 0x301);
+    assert_eq!(vm.v[0].0, 0xFF);
+}
+
+#[test]
+fn test_clip_quirk() {
+    let mut vm = init_vm_with(Quirks::cosmac_vip());
+
+    vm.i = 0x300;
+    vm.ram[0x300] = 0xFF;
+    vm.v[3] = Wrapping(60);
+    vm.v[4] = Wrapping(0);
+    vm.draw(0x3, 0x4, 1).unwrap();
+
+    assert_eq!(vm.display_data.data[60], 1);
+    assert_eq!(vm.display_data.data[0], 0);
+
+    let mut vm = init_vm_with(Quirks::modern());
+
+    vm.i = 0x300;
+    vm.ram[0x300] = 0xFF;
+    vm.v[3] = Wrapping(60);
+    vm.v[4] = Wrapping(0);
+    vm.draw(0x3, 0x4, 1).unwrap();
+
+    assert_eq!(vm.display_data.data[0], 1);
+}
+
+#[test]
+fn test_schip_opcodes_need_platform() {
+    let mut vm = init_vm();
+
+    vm.ram[0x200] = 0x00;
+    vm.ram[0x201] = 0xFF;
+
+    match vm.step() {
+        Err(VmError::UnknownOpcode { opcode, .. }) => assert_eq!(opcode, 0x00FF),
+        _ => panic!("Expected 00FF to be rejected on CHIP-8."),
+    }
+
+    let mut vm = init_schip_vm();
+
+    vm.ram[0x200] = 0x00;
+    vm.ram[0x201] = 0xFF;
+    vm.step().unwrap();
+
+    assert!(vm.display_data.is_hires());
+    assert_eq!(vm.display_data.data.len(), 128 * 64);
+}
+
+#[test]
+fn test_scroll() {
+    let mut vm = init_schip_vm();
+
+    vm.display_data.data[0] = 1;
+    vm.scroll_down(2);
+
+    assert_eq!(vm.display_data.data[0], 0);
+    assert_eq!(vm.display_data.data[2 * 64], 1);
+
+    vm.scroll_right();
+
+    assert_eq!(vm.display_data.data[2 * 64 + 4], 1);
+
+    vm.scroll_left();
+    vm.scroll_left();
+
+    assert_eq!(vm.display_data.data[2 * 64 + 60], 0);
+    assert!(vm.display_data.data.iter().all(|pixel| *pixel == 0));
+}
+
+#[test]
+fn test_large_sprite() {
+    let mut vm = init_schip_vm();
+
+    vm.hires();
+    vm.i = 0x300;
+    vm.ram[0x300] = 0x80;
+    vm.ram[0x301] = 0x01;
+    vm.v[3] = Wrapping(0);
+    vm.draw(0x3, 0x3, 0).unwrap();
+
+    assert_eq!(vm.display_data.data[0], 1);
+    assert_eq!(vm.display_data.data[15], 1);
+    assert_eq!(vm.v[0xF].0, 0);
+
+    vm.large_sprite_addr(0x2);
+
+    assert_eq!(vm.i, 0xA0 + 10 * 0xE);
+}
+
+#[test]
+fn test_rpl_flags() {
+    let mut vm = init_schip_vm();
+
+    vm.save_flags(0xF).unwrap();
+    vm.v[0] = Wrapping(0);
+    vm.v[2] = Wrapping(0);
+    vm.load_flags(0x2);
+
+    assert_eq!(vm.v[0].0, 0xFF);
+    assert_eq!(vm.v[2].0, 0xE);
+    assert_eq!(vm.rpl[8], 0);
+}
+
+#[test]
+fn test_xochip_memory() {
+    let mut vm = init_xochip_vm();
+
+    vm.load_rom_bytes(&[0xAB; 0x10000 - 0x200]).unwrap();
+
+    assert_eq!(vm.ram[0xFFFF], 0xAB);
+
+    vm.ram[0x200] = 0xF0;
+    vm.ram[0x201] = 0x00;
+    vm.ram[0x202] = 0xBE;
+    vm.ram[0x203] = 0xEF;
+    vm.step().unwrap();
+
+    assert_eq!(vm.i, 0xBEEF);
+    assert_eq!(vm.pc, 0x204);
+}
+
+#[test]
+fn test_skip_long_instruction() {
+    let mut vm = init_xochip_vm();
+
+    vm.ram[0x202] = 0xF0;
+    vm.ram[0x203] = 0x00;
+    vm.skip_b_eq(0x0, 0xFF).unwrap();
+
+    assert_eq!(vm.pc, 0x204);
+}
+
+#[test]
+fn test_register_range() {
+    let mut vm = init_xochip_vm();
+
+    vm.i = 0x300;
+    vm.save_range(0x0, 0x2).unwrap();
+
+    assert_eq!(vm.ram[0x300..0x303], [0xFF, 0x20, 0xE]);
+
+    vm.i = 0x300;
+    vm.load_range(0x2, 0x0).unwrap();
+
+    assert_eq!(vm.v[2].0, 0xFF);
+    assert_eq!(vm.v[1].0, 0x20);
+    assert_eq!(vm.v[0].0, 0xE);
+}
+
+#[test]
+fn test_planes() {
+    let mut vm = init_xochip_vm();
+
+    vm.select_planes(3);
+    vm.i = 0x300;
+    vm.ram[0x300] = 0x80;
+    vm.ram[0x301] = 0xC0;
+    vm.v[3] = Wrapping(0);
+    vm.draw(0x3, 0x3, 1).unwrap();
+
+    assert_eq!(vm.display_data.data[0], 3);
+    assert_eq!(vm.display_data.data[1], 2);
+
+    vm.select_planes(1);
+    vm.clear();
+
+    assert_eq!(vm.display_data.data[0], 2);
+    assert_eq!(vm.display_data.data[1], 2);
+}
+
+#[test]
+fn test_audio_pattern() {
+    let mut vm = init_xochip_vm();
+
+    vm.i = 0x300;
+    vm.ram[0x30F] = 0xAA;
+    vm.load_audio_pattern().unwrap();
+    vm.put_pitch_x(0x1);
+
+    assert_eq!(vm.audio_pattern()[15], 0xAA);
+    assert_eq!(vm.pitch(), 0x20);
+}
+
+#[test]
+fn test_hires_chip8() {
+    let rom = fs::read("roms/hires/Hires Test [Tom Swan, 1979].ch8").unwrap();
+
+    assert_eq!(Platform::detect(&rom), Platform::HiresChip8);
+    assert_eq!(Platform::detect(&[0x00, 0xE0]), Platform::Chip8);
+
+    let mut vm = Chip8Vm::with_platform(
+        Box::new(MockFrontEnd::default()),
+        Platform::HiresChip8,
+        Quirks::cosmac_vip(),
+    );
+    vm.load_rom_bytes(&rom).unwrap();
+    vm.step().unwrap();
+
+    assert_eq!(vm.pc, 0x2C0);
+    assert_eq!(vm.display_data.width(), 64);
+    assert_eq!(vm.display_data.height(), 64);
+
+    vm.display_data.data[64 * 63] = 1;
+    vm.ram[0x2C0] = 0x02;
+    vm.ram[0x2C1] = 0x30;
+    vm.step().unwrap();
+
+    assert_eq!(vm.display_data.data[64 * 63], 0);
+}
+
+#[test]
+fn test_buzzer() {
+    let buzzer = Rc::new(Cell::new(None));
+    let mut vm = Chip8Vm::new(
+        Box::new(BuzzerFrontEnd {
+            keys: Keys::default(),
+            buzzer: buzzer.clone(),
+            audio: Rc::default(),
+        }),
+        Quirks::default(),
+    );
+
+    vm.ram[0x200] = 0xF0;
+    vm.ram[0x201] = 0x18;
+    vm.ram[0x202] = 0x12;
+    vm.ram[0x203] = 0x02;
+    vm.v[0] = Wrapping(1);
+    vm.run_frame(1).unwrap();
+
+    assert_eq!(buzzer.get(), Some(true));
+
+    vm.run_frame(1).unwrap();
+
+    assert_eq!(buzzer.get(), Some(false));
+}
+
+#[test]
+fn test_audio_hook() {
+    let audio = Rc::new(Cell::new(None));
+    let mut vm = Chip8Vm::with_platform(
+        Box::new(BuzzerFrontEnd {
+            keys: Keys::default(),
+            buzzer: Rc::default(),
+            audio: audio.clone(),
+        }),
+        Platform::XoChip,
+        Quirks::modern(),
+    );
+
+    // LD I, 0x300; AUDIO; LD V0, 0x50; PITCH V0; JP 0x208
+    let program = [0xA3, 0x00, 0xF0, 0x02, 0x60, 0x50, 0xF0, 0x3A, 0x12, 0x08];
+    vm.load_rom_bytes(&program).unwrap();
+    vm.ram[0x300] = 0xF0;
+    vm.run_frame(1).unwrap();
+
+    assert_eq!(audio.get(), None);
+
+    vm.run_frame(4).unwrap();
+
+    let mut pattern = [0; AUDIO_PATTERN_SIZE];
+    pattern[0] = 0xF0;
+    assert_eq!(audio.get(), Some((pattern, 0x50)));
+
+    audio.set(None);
+    vm.run_frame(1).unwrap();
+
+    assert_eq!(audio.get(), None);
+}
+
+#[test]
+fn test_run_frame() {
+    let mut vm = init_vm();
+
+    vm.dt = 10;
+    vm.ram[0x200] = 0x12;
+    vm.ram[0x201] = 0x00;
+    vm.run_frame(100).unwrap();
+
+    assert_eq!(vm.dt, 9);
+    assert_eq!(vm.pc, 0x200);
+}
+
+#[test]
+fn test_wait_for_keypress() {
+    let mut vm = init_vm();
+
+    vm.ram[0x200] = 0xF3;
+    vm.ram[0x201] = 0x0A;
+    vm.step().unwrap();
+
+    assert_eq!(vm.pc, 0x200);
+
+    vm.keys[0xB] = Key(true);
+    vm.step().unwrap();
+
+    assert_eq!(vm.pc, 0x202);
+    assert_eq!(vm.v[3].0, 0xB);
+}
+
+#[test]
+fn test_seeded_rng() {
+    let mut first = init_vm();
+    let mut second = init_vm();
+
+    first.set_seed(1234);
+    second.set_seed(1234);
+    let mut position = 0;
+    let rolls: Vec<u8> = (0..8)
+        .map(|roll| {
+            if roll == 4 {
+                position = first.rng_position();
+            }
+            first.rand(0x0, 0xFF);
+            first.v[0].0
+        })
+        .collect();
+    let replayed: Vec<u8> = (0..8)
+        .map(|_| {
+            second.rand(0x0, 0xFF);
+            second.v[0].0
+        })
+        .collect();
+
+    assert_eq!(rolls, replayed);
+    assert_eq!(first.seed(), Some(1234));
+
+    second.restore_rng(1234, position);
+    second.rand(0x0, 0xFF);
+
+    assert_eq!(second.v[0].0, rolls[4]);
+}
+
+#[test]
+fn test_injected_rng() {
+    let mut vm = init_vm();
+
+    vm.set_rng(Box::new(StepRng::new(0xAB, 0)));
+    vm.rand(0x0, 0x0F);
+
+    assert_eq!(vm.v[0].0, 0xB);
+    assert_eq!(vm.seed(), None);
+}
+
+#[test]
+fn test_headless_frontend() {
+    let frontend = HeadlessFrontEnd::default();
+    let mut vm = Chip8Vm::new(Box::new(frontend.clone()), Quirks::default());
+
+    vm.load_rom_bytes(&[0xF0, 0x0A, 0xF0, 0x29, 0xD1, 0x15, 0x12, 0x06])
+        .unwrap();
+    frontend.press_key(3, 0x7).unwrap();
+    vm.set_rewind_frames(5);
+    for _ in 0..5 {
+        vm.run_frame(10).unwrap();
+    }
+
+    assert_eq!(frontend.frames(), 5);
+    assert_eq!(vm.v[0].0, 0x7);
+    assert_eq!(
+        frontend.screen_text().lines().take(5).collect::<Vec<_>>(),
+        ["####", "...#", "..#.", ".#..", ".#.."]
+            .iter()
+            .map(|row| format!("{:.<64}", row))
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(frontend.screen_bytes()[..4], [1, 1, 1, 1]);
+
+    assert_eq!(frontend.press_key(4, 0x10), Err(InvalidKey { key: 0x10 }));
+
+    vm.idle();
+
+    assert_eq!(frontend.frames(), 5);
+
+    assert_eq!(vm.rewind(2).unwrap(), 2);
+
+    assert_eq!(frontend.frames(), 3);
+}
+
 #[test]
 fn test_put_i_adrr() {
     let mut vm = init_vm();
@@ -316,12 +835,363 @@ fn test_put_i_adrr() {
     assert_eq!(vm.i, 0x200);
 }
 
+#[test]
+fn test_decode() {
+    assert_eq!(
+        Instruction::decode(0xD125, Platform::Chip8),
+        Ok(Instruction::Draw {
+            x: 1,
+            y: 2,
+            rows: 5
+        })
+    );
+    assert_eq!(
+        Instruction::decode(0x8AB6, Platform::Chip8),
+        Ok(Instruction::ShiftRight { x: 0xA, y: 0xB })
+    );
+    assert!(Instruction::decode(0x00FF, Platform::Chip8).is_err());
+    assert_eq!(
+        Instruction::decode(0x00FF, Platform::SuperChip),
+        Ok(Instruction::Hires)
+    );
+    assert!(Instruction::decode(0xF000, Platform::SuperChip).is_err());
+    assert_eq!(
+        Instruction::decode(0xF000, Platform::XoChip),
+        Ok(Instruction::LoadLongI)
+    );
+    assert_eq!(
+        Instruction::decode(0x8AB8, Platform::XoChip),
+        Err(DecodeError { opcode: 0x8AB8 })
+    );
+}
+
+#[test]
+fn test_encode() {
+    for platform in [Platform::Chip8, Platform::SuperChip, Platform::XoChip] {
+        for opcode in 0..=0xFFFF {
+            if let Ok(instruction) = Instruction::decode(opcode, platform) {
+                assert_eq!(
+                    Instruction::decode(instruction.encode(), platform),
+                    Ok(instruction)
+                );
+            }
+        }
+    }
+    assert_eq!(Instruction::LoadI(0x2EA).encode(), 0xA2EA);
+    assert_eq!(
+        Instruction::Draw {
+            x: 1,
+            y: 2,
+            rows: 5
+        }
+        .encode(),
+        0xD125
+    );
+}
+
+#[test]
+fn test_fetch() {
+    let memory = [0xF0, 0x00, 0x12, 0x34, 0xFF, 0xFF];
+    let fetched = Fetched::read_at(&memory, 0, Platform::XoChip).unwrap();
+
+    assert_eq!(fetched.operand, Some(0x1234));
+    assert_eq!(fetched.size(), 4);
+    assert_eq!(fetched.to_string(), "LD I, LONG 0x1234");
+
+    let truncated = Fetched::read_at(&memory[..3], 0, Platform::XoChip).unwrap();
+
+    assert_eq!(truncated.size(), 2);
+    assert_eq!(truncated.to_string(), "LD I, LONG");
+    assert_eq!(
+        Fetched::read_at(&memory, 4, Platform::XoChip).map(|fetched| fetched.to_string()),
+        Some("???".to_owned())
+    );
+    assert_eq!(Fetched::read_at(&memory, 5, Platform::XoChip), None);
+}
+
+#[test]
+fn test_unknown_opcode() {
+    let mut vm = init_vm();
+
+    vm.ram[0x200] = 0xFF;
+    vm.ram[0x201] = 0xFF;
+
+    match vm.step() {
+        Err(VmError::UnknownOpcode { opcode, pc }) => {
+            assert_eq!(opcode, 0xFFFF);
+            assert_eq!(pc, 0x200);
+        }
+        _ => panic!("Expected an unknown opcode error."),
+    }
+}
+
+#[test]
+fn test_pc_out_of_bounds() {
+    let mut vm = init_xochip_vm();
+
+    vm.pc = 0xFFFE;
+    vm.ram[0xFFFE] = 0x60;
+
+    match vm.step() {
+        Err(VmError::PcOutOfBounds { pc }) => assert_eq!(pc, 0xFFFE),
+        _ => panic!("Expected a program counter error."),
+    }
+
+    let mut vm = init_xochip_vm();
+
+    vm.pc = 0xFFFC;
+    vm.ram[0xFFFC] = 0x30;
+    vm.ram[0xFFFD] = 0xFF;
+
+    match vm.step() {
+        Err(VmError::PcOutOfBounds { pc }) => assert_eq!(pc, 0xFFFC),
+        _ => panic!("Expected a program counter error."),
+    }
+
+    let mut vm = init_xochip_vm();
+
+    vm.pc = 0xFFFC;
+    vm.ram[0xFFFC] = 0xF0;
+
+    match vm.step() {
+        Err(VmError::PcOutOfBounds { pc }) => assert_eq!(pc, 0xFFFC),
+        _ => panic!("Expected a program counter error."),
+    }
+}
+
+#[test]
+fn test_memory_out_of_bounds() {
+    let mut vm = init_vm();
+
+    vm.i = 0xFFE;
+
+    match vm.bcd(0x0) {
+        Err(VmError::MemoryOutOfBounds { address, .. }) => assert_eq!(address, 0x1000),
+        _ => panic!("Expected an out of bounds error."),
+    }
+}
+
+#[test]
+fn test_address_policy() {
+    let mut vm = init_vm();
+
+    vm.i = 0xFFF;
+    vm.set_address_policy(AddressPolicy::Wrap);
+    vm.bcd(0x0).unwrap();
+
+    assert_eq!(vm.ram[0xFFF], 2);
+    assert_eq!(vm.ram[0x000], 5);
+    assert_eq!(vm.ram[0x001], 5);
+
+    let mut vm = init_vm();
+
+    vm.i = 0xFFF;
+    vm.set_address_policy(AddressPolicy::Clamp);
+    vm.bcd(0x0).unwrap();
+
+    assert_eq!(vm.ram[0xFFF], 5);
+}
+
+#[test]
+fn test_load_rom() {
+    let mut vm = init_vm();
+
+    vm.load_rom_reader(&[0x12, 0x34][..]).unwrap();
+
+    assert_eq!(vm.ram[0x200], 0x12);
+    assert_eq!(vm.ram[0x201], 0x34);
+
+    vm.load_rom_bytes(&[0xAA; 0xE00]).unwrap();
+
+    assert_eq!(vm.ram[0xFFF], 0xAA);
+
+    match vm.load_rom_bytes(&[0; 0xE01]) {
+        Err(VmError::RomTooLarge { size, max }) => {
+            assert_eq!(size, 0xE01);
+            assert_eq!(max, 0xE00);
+        }
+        _ => panic!("Expected the rom to be rejected."),
+    }
+}
+
+#[test]
+fn test_save_state() {
+    let mut vm = init_schip_vm();
+    vm.load_rom_bytes(&[0x00, 0xFF, 0xD0, 0x10]).unwrap();
+    vm.set_seed(7);
+    vm.rand(0x3, 0xFF);
+    vm.call(0x300).unwrap();
+    vm.ram[0x400] = 0x42;
+    vm.hires();
+    vm.display_data.data[5] = 1;
+    vm.dt = 30;
+    let state = vm.save_state();
+
+    let header = StateHeader::parse(&state).unwrap();
+    assert_eq!(header.platform, Platform::SuperChip);
+    assert_eq!(header.quirks, Quirks::schip());
+    assert_eq!(header.rom_hash, vm.rom_hash());
+
+    vm.rand(0x1, 0xFF);
+    let expected = vm.v[1];
+    vm.v[0] = Wrapping(0);
+    vm.ret().unwrap();
+    vm.ram[0x400] = 0;
+    vm.lores();
+    vm.dt = 0;
+
+    vm.load_state(&state).unwrap();
+
+    assert_eq!(vm.v[0], Wrapping(0xFF));
+    assert_eq!(vm.pc, 0x300);
+    assert_eq!(vm.stack(), &[0x200]);
+    assert_eq!(vm.ram[0x400], 0x42);
+    assert!(vm.display_data.is_hires());
+    assert_eq!(vm.display_data.data[5], 1);
+    assert_eq!(vm.dt, 30);
+    vm.rand(0x1, 0xFF);
+    assert_eq!(vm.v[1], expected);
+}
+
+#[test]
+fn test_load_invalid_state() {
+    let mut vm = init_schip_vm();
+    vm.load_rom_bytes(&[0x00, 0xFF]).unwrap();
+    let state = vm.save_state();
+
+    assert!(matches!(
+        vm.load_state(b"not a state"),
+        Err(VmError::InvalidState(_))
+    ));
+    assert!(matches!(
+        vm.load_state(&state[..state.len() - 1]),
+        Err(VmError::InvalidState(_))
+    ));
+
+    let mut other_rom = init_schip_vm();
+    other_rom.load_rom_bytes(&[0x00, 0xFE]).unwrap();
+    assert!(matches!(
+        other_rom.load_state(&state),
+        Err(VmError::InvalidState(_))
+    ));
+
+    let mut other_platform = init_xochip_vm();
+    other_platform.load_rom_bytes(&[0x00, 0xFF]).unwrap();
+    assert!(matches!(
+        other_platform.load_state(&state),
+        Err(VmError::InvalidState(_))
+    ));
+
+    let display = state.len() - LORES_WIDTH * LORES_HEIGHT - 5;
+    let with_display = |width: usize, height: usize, planes: u8| {
+        let mut state = state[..display].to_vec();
+        state.extend_from_slice(&(width as u16).to_le_bytes());
+        state.extend_from_slice(&(height as u16).to_le_bytes());
+        state.push(planes);
+        state.extend(vec![0; width * height]);
+        state
+    };
+    assert!(vm.load_state(&with_display(128, 64, 1)).is_ok());
+    for (width, height, planes) in &[(0, 0, 1), (3, 3, 1), (64, 64, 1), (64, 32, 3)] {
+        assert!(matches!(
+            vm.load_state(&with_display(*width, *height, *planes)),
+            Err(VmError::InvalidState(_))
+        ));
+    }
+}
+
+#[test]
+fn test_rewind() {
+    let mut vm = init_vm();
+    vm.load_rom_bytes(&[0x70, 0x01, 0x12, 0x00]).unwrap();
+    vm.v[0] = Wrapping(0);
+    vm.set_rewind_frames(3);
+
+    assert_eq!(vm.rewind(1).unwrap(), 0);
+
+    for _ in 0..6 {
+        vm.run_frame(2).unwrap();
+    }
+    assert_eq!(vm.v[0], Wrapping(6));
+
+    assert_eq!(vm.rewind(2).unwrap(), 2);
+    assert_eq!(vm.v[0], Wrapping(4));
+
+    vm.run_frame(2).unwrap();
+    assert_eq!(vm.v[0], Wrapping(5));
+
+    assert_eq!(vm.rewind(10).unwrap(), 2);
+    assert_eq!(vm.v[0], Wrapping(3));
+}
+
+#[test]
+fn test_trace() {
+    let run = |tracer: Tracer| {
+        let mut vm = init_vm();
+        vm.load_rom_bytes(&[0x60, 0x05, 0x80, 0x04, 0xA3, 0x00, 0xFF, 0xFF])
+            .unwrap();
+        vm.set_tracer(tracer);
+        assert!(vm.run_frame(10).is_err());
+        assert_eq!(vm.take_tracer().unwrap().cycle(), 4);
+    };
+
+    let output = SharedBuffer::default();
+    run(Tracer::new(Box::new(output.clone())));
+    assert_eq!(
+        output.text(),
+        "       0  200  6005  LD V0, 0x05           V0=05
+       1  202  8004  ADD V0, V0            V0=0A
+       2  204  A300  LD I, 0x300           I=0300
+       3  206  FFFF  ???                   fault: unknown opcode FFFF at 206
+"
+    );
+
+    let output = SharedBuffer::default();
+    let mut tracer = Tracer::new(Box::new(output.clone()));
+    tracer.set_range(0x204..=0x206);
+    run(tracer);
+    assert_eq!(output.text().lines().count(), 2);
+    assert!(output.text().starts_with("       2  204"));
+
+    let output = SharedBuffer::default();
+    let mut tracer = Tracer::new(Box::new(output.clone()));
+    tracer.set_ring_size(2);
+    run(tracer);
+    let lines: Vec<String> = output.text().lines().map(str::to_owned).collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].starts_with("       2  204"));
+    assert!(lines[1].ends_with("unknown opcode FFFF at 206"));
+}
+
+#[derive(Clone, Default)]
+struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl SharedBuffer {
+    fn text(&self) -> String {
+        String::from_utf8(self.0.borrow().clone()).unwrap()
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
 fn init_vm() -> Chip8Vm {
-    let mut vm = Chip8Vm::new(Box::new(MockFrontEnd::default()));
+    init_vm_with(Quirks::legacy())
+}
+
+fn init_vm_with(quirks: Quirks) -> Chip8Vm {
+    let mut vm = Chip8Vm::new(Box::new(MockFrontEnd::default()), quirks);
     vm.pc = 0x200;
     vm.stack[0] = 0xFFF;
     vm.sp = 1;
-    use std::num::Wrapping;
     vm.v[0] = Wrapping(0xFF);
     vm.v[1] = Wrapping(0x20);
     vm.v[2] = Wrapping(0xE);
@@ -329,6 +1199,60 @@ fn init_vm() -> Chip8Vm {
     vm
 }
 
+fn init_schip_vm() -> Chip8Vm {
+    let mut vm = Chip8Vm::with_platform(
+        Box::new(MockFrontEnd::default()),
+        Platform::SuperChip,
+        Quirks::schip(),
+    );
+    vm.v[0] = Wrapping(0xFF);
+    vm.v[1] = Wrapping(0x20);
+    vm.v[2] = Wrapping(0xE);
+    vm
+}
+
+fn init_xochip_vm() -> Chip8Vm {
+    let mut vm = Chip8Vm::with_platform(
+        Box::new(MockFrontEnd::default()),
+        Platform::XoChip,
+        Quirks::modern(),
+    );
+    vm.v[0] = Wrapping(0xFF);
+    vm.v[1] = Wrapping(0x20);
+    vm.v[2] = Wrapping(0xE);
+    vm
+}
+
+type AudioPattern = ([u8; AUDIO_PATTERN_SIZE], u8);
+
+struct BuzzerFrontEnd {
+    keys: Keys,
+    buzzer: Rc<Cell<Option<bool>>>,
+    audio: Rc<Cell<Option<AudioPattern>>>,
+}
+
+impl FrontEnd for BuzzerFrontEnd {
+    fn draw(&mut self, _data: &DisplayBuffer) {}
+
+    fn get_keys(&mut self) -> &Keys {
+        &self.keys
+    }
+
+    fn set_buzzer(&mut self, on: bool) {
+        self.buzzer.set(Some(on));
+    }
+
+    fn set_audio_pattern(&mut self, pattern: &[u8; AUDIO_PATTERN_SIZE], pitch: u8) {
+        self.audio.set(Some((*pattern, pitch)));
+    }
+
+    fn should_quit(&self) -> bool {
+        false
+    }
+
+    fn update(&mut self) {}
+}
+
 fn init_pc() -> &'static str {
     "Initial program counter."
 }
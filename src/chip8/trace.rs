@@ -0,0 +1,136 @@
+use super::{Chip8Vm, Fetched, VmError};
+
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::ops::RangeInclusive;
+
+pub struct Tracer {
+    output: Box<dyn Write>,
+    range: Option<RangeInclusive<u16>>,
+    ring_size: usize,
+    ring: VecDeque<String>,
+    cycle: u64,
+}
+
+pub(crate) struct Snapshot {
+    pc: u16,
+    fetched: Option<Fetched>,
+    v: [u8; 16],
+    i: u16,
+    sp: usize,
+    dt: u8,
+    st: u8,
+}
+
+impl Snapshot {
+    pub(crate) fn new(vm: &Chip8Vm) -> Self {
+        Self {
+            pc: vm.pc(),
+            fetched: Fetched::read_at(vm.memory(), vm.pc() as usize, vm.platform()),
+            v: vm.v(),
+            i: vm.i(),
+            sp: vm.stack().len(),
+            dt: vm.delay_timer(),
+            st: vm.sound_timer(),
+        }
+    }
+
+    fn changes(&self, after: &Snapshot) -> Vec<String> {
+        let mut changes = Vec::new();
+        for (x, (before, after)) in self.v.iter().zip(&after.v).enumerate() {
+            if before != after {
+                changes.push(format!("V{:X}={:02X}", x, after));
+            }
+        }
+        if self.i != after.i {
+            changes.push(format!("I={:04X}", after.i));
+        }
+        if self.sp != after.sp {
+            changes.push(format!("SP={}", after.sp));
+        }
+        if self.dt != after.dt {
+            changes.push(format!("DT={:02X}", after.dt));
+        }
+        if self.st != after.st {
+            changes.push(format!("ST={:02X}", after.st));
+        }
+        changes
+    }
+}
+
+impl Tracer {
+    pub fn new(output: Box<dyn Write>) -> Self {
+        Self {
+            output,
+            range: None,
+            ring_size: 0,
+            ring: VecDeque::new(),
+            cycle: 0,
+        }
+    }
+
+    pub fn set_range(&mut self, range: RangeInclusive<u16>) {
+        self.range = Some(range);
+    }
+
+    pub fn set_ring_size(&mut self, size: usize) {
+        self.ring_size = size;
+        self.ring.clear();
+    }
+
+    pub fn cycle(&self) -> u64 {
+        self.cycle
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.output.flush()
+    }
+
+    pub(crate) fn record(
+        &mut self,
+        before: &Snapshot,
+        vm: &Chip8Vm,
+        result: &Result<(), VmError>,
+    ) -> io::Result<()> {
+        let cycle = self.cycle;
+        self.cycle += 1;
+        let in_range = self
+            .range
+            .as_ref()
+            .map_or(true, |range| range.contains(&before.pc));
+        if result.is_ok() && !in_range {
+            return Ok(());
+        }
+
+        let (opcode, mnemonic) = match before.fetched {
+            Some(fetched) => (fetched.opcode, fetched.to_string()),
+            None => (0, "???".to_owned()),
+        };
+        let details = match result {
+            Ok(()) => before.changes(&Snapshot::new(vm)).join(" "),
+            Err(error) => format!("fault: {}", error),
+        };
+        let line = format!(
+            "{:>8}  {:03X}  {:04X}  {:<20}  {}",
+            cycle, before.pc, opcode, mnemonic, details
+        );
+        let line = line.trim_end();
+
+        if self.ring_size == 0 {
+            return writeln!(self.output, "{}", line);
+        }
+        if result.is_err() {
+            let skip = (self.ring.len() + 1).saturating_sub(self.ring_size);
+            for traced in self.ring.drain(..).skip(skip) {
+                writeln!(self.output, "{}", traced)?;
+            }
+            writeln!(self.output, "{}", line)?;
+            return self.output.flush();
+        }
+        if self.ring.len() == self.ring_size {
+            self.ring.pop_front();
+        }
+        self.ring.push_back(line.to_owned());
+        Ok(())
+    }
+}
@@ -0,0 +1,287 @@
+use yaci::chip8::{Platform, Quirks, DEFAULT_CPU_HZ, DEFAULT_REWIND_FRAMES, MAX_STACK_DEPTH};
+use yaci::sdl2::{DEFAULT_TONE_HZ, DEFAULT_VOLUME};
+
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+
+pub const USAGE: &str = "\
+usage: yaci [options] <rom>
+       yaci debug [options] <rom>
+       yaci disasm [--platform <name>] <rom>
+       yaci asm [-o <rom>] [--listing <file>] <source>
+
+options:
+    --scale <n>          window pixels per chip-8 pixel (default 20)
+    --cpu-hz <n>         instructions executed per second (default 700)
+    --platform <name>    chip8, hires, schip or xochip (default detected from rom)
+    --quirks <profile>   legacy, vip, chip48, schip or modern (default per platform)
+    --stack-depth <n>    nested calls allowed, 12 on the COSMAC VIP (default 16)
+    --tone-hz <n>        buzzer frequency in hertz (default 440)
+    --volume <n>         buzzer volume from 0.0 to 1.0 (default 0.25)
+    --headless           run without opening a window, as fast as possible
+    --seed <n>           seed for the random number generator
+    --frames <n>         stop after n 60 Hz frames
+    --dump-screen        print the screen to stdout on exit
+    --screenshot <file>  save the screen as png, pbm or pgm on exit
+    --rewind <n>         frames kept for rewinding, 0 disables (default 600,
+                         or 0 with --headless)
+    --record <file>      record the keys pressed each frame to a movie file
+    --replay <file>      play back a movie recorded with --record
+    --trace <file>       log every executed instruction, - for stdout
+    --trace-range <a-b>  only trace instructions between two hex addresses
+    --trace-ring <n>     keep the last n traced instructions and write them
+                         when the rom faults (to stderr without --trace)
+    --gdb <port>         with debug, wait for gdb on a local port instead of
+                         starting the command prompt
+    -h, --help           print this message
+
+keys:
+    F1-F4                load state from slot 1-4
+    Shift+F1-F4          save state to slot 1-4
+    Backspace            hold to rewind
+    F9                   toggle the debug panel
+    F10                  pause or continue
+    F11                  step one instruction while paused
+    F12                  save a screenshot
+    Escape               quit
+
+exit status:
+    1                    the rom faulted
+    2                    invalid arguments, rom, movie or assembly source
+    3                    writing an output file or the gdb connection failed";
+
+pub struct Options {
+    pub rom: PathBuf,
+    pub scale: u32,
+    pub cpu_hz: u32,
+    pub platform: Option<Platform>,
+    pub quirks: Option<Quirks>,
+    pub stack_depth: usize,
+    pub tone_hz: f32,
+    pub volume: f32,
+    pub headless: bool,
+    pub seed: Option<u64>,
+    pub frames: Option<u64>,
+    pub dump_screen: bool,
+    pub screenshot: Option<PathBuf>,
+    pub rewind_frames: usize,
+    pub record: Option<PathBuf>,
+    pub replay: Option<PathBuf>,
+    pub trace: Option<PathBuf>,
+    pub trace_range: Option<RangeInclusive<u16>>,
+    pub trace_ring: usize,
+    pub gdb_port: Option<u16>,
+}
+
+pub enum Command {
+    Run(Options),
+    Debug(Options),
+    Disasm {
+        rom: PathBuf,
+        platform: Option<Platform>,
+    },
+    Asm {
+        source: PathBuf,
+        output: PathBuf,
+        listing: Option<PathBuf>,
+    },
+    Help,
+}
+
+impl Command {
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Command, String> {
+        let mut args = args.into_iter().peekable();
+        match args.peek().map(String::as_str) {
+            Some("disasm") => {
+                args.next();
+                Self::parse_disasm(args)
+            }
+            Some("asm") => {
+                args.next();
+                Self::parse_asm(args)
+            }
+            Some("debug") => {
+                args.next();
+                Self::parse_run(args, true)
+            }
+            _ => Self::parse_run(args, false),
+        }
+    }
+
+    fn parse_run(args: impl Iterator<Item = String>, debug: bool) -> Result<Command, String> {
+        let mut args = args.into_iter();
+        let mut rom = None;
+        let mut rewind_frames = None;
+        let mut options = Options {
+            rom: PathBuf::new(),
+            scale: 20,
+            cpu_hz: DEFAULT_CPU_HZ,
+            platform: None,
+            quirks: None,
+            stack_depth: MAX_STACK_DEPTH,
+            tone_hz: DEFAULT_TONE_HZ,
+            volume: DEFAULT_VOLUME,
+            headless: false,
+            seed: None,
+            frames: None,
+            dump_screen: false,
+            screenshot: None,
+            rewind_frames: 0,
+            record: None,
+            replay: None,
+            trace: None,
+            trace_range: None,
+            trace_ring: 0,
+            gdb_port: None,
+        };
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-h" | "--help" => return Ok(Command::Help),
+                "--scale" => options.scale = parse_value(&arg, args.next())?,
+                "--cpu-hz" => options.cpu_hz = parse_value(&arg, args.next())?,
+                "--platform" => options.platform = Some(parse_platform(args.next())?),
+                "--quirks" => options.quirks = Some(parse_quirks(args.next())?),
+                "--stack-depth" => options.stack_depth = parse_value(&arg, args.next())?,
+                "--tone-hz" => options.tone_hz = parse_value(&arg, args.next())?,
+                "--volume" => options.volume = parse_value(&arg, args.next())?,
+                "--headless" => options.headless = true,
+                "--seed" => options.seed = Some(parse_value(&arg, args.next())?),
+                "--frames" => options.frames = Some(parse_value(&arg, args.next())?),
+                "--dump-screen" => options.dump_screen = true,
+                "--screenshot" => options.screenshot = Some(parse_value(&arg, args.next())?),
+                "--rewind" => rewind_frames = Some(parse_value(&arg, args.next())?),
+                "--record" => options.record = Some(parse_value(&arg, args.next())?),
+                "--replay" => options.replay = Some(parse_value(&arg, args.next())?),
+                "--trace" => options.trace = Some(parse_value(&arg, args.next())?),
+                "--trace-range" => options.trace_range = Some(parse_range(args.next())?),
+                "--trace-ring" => options.trace_ring = parse_value(&arg, args.next())?,
+                "--gdb" => options.gdb_port = Some(parse_value(&arg, args.next())?),
+                _ if arg.starts_with('-') => return Err(format!("unknown option '{}'", arg)),
+                _ if rom.is_some() => return Err(format!("unexpected argument '{}'", arg)),
+                _ => rom = Some(PathBuf::from(arg)),
+            }
+        }
+
+        if options.scale == 0 || options.cpu_hz == 0 {
+            return Err("--scale and --cpu-hz must be greater than zero".to_owned());
+        }
+        if options.stack_depth == 0 || options.stack_depth > MAX_STACK_DEPTH {
+            return Err(format!(
+                "--stack-depth must be between 1 and {}",
+                MAX_STACK_DEPTH
+            ));
+        }
+        if options.record.is_some() && options.replay.is_some() {
+            return Err("--record and --replay cannot be used together".to_owned());
+        }
+        if debug && (options.record.is_some() || options.replay.is_some()) {
+            return Err("--record and --replay cannot be used with debug".to_owned());
+        }
+        if !debug && options.gdb_port.is_some() {
+            return Err("--gdb can only be used with debug".to_owned());
+        }
+        options.rom = rom.ok_or_else(|| "missing rom path".to_owned())?;
+        options.rewind_frames = rewind_frames.unwrap_or(if options.headless {
+            0
+        } else {
+            DEFAULT_REWIND_FRAMES
+        });
+
+        if debug {
+            Ok(Command::Debug(options))
+        } else {
+            Ok(Command::Run(options))
+        }
+    }
+
+    fn parse_disasm(args: impl Iterator<Item = String>) -> Result<Command, String> {
+        let mut args = args.into_iter();
+        let mut rom = None;
+        let mut platform = None;
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-h" | "--help" => return Ok(Command::Help),
+                "--platform" => platform = Some(parse_platform(args.next())?),
+                _ if arg.starts_with('-') => return Err(format!("unknown option '{}'", arg)),
+                _ if rom.is_some() => return Err(format!("unexpected argument '{}'", arg)),
+                _ => rom = Some(PathBuf::from(arg)),
+            }
+        }
+
+        Ok(Command::Disasm {
+            rom: rom.ok_or_else(|| "missing rom path".to_owned())?,
+            platform,
+        })
+    }
+
+    fn parse_asm(args: impl Iterator<Item = String>) -> Result<Command, String> {
+        let mut args = args.into_iter();
+        let mut source = None;
+        let mut output = None;
+        let mut listing = None;
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-h" | "--help" => return Ok(Command::Help),
+                "-o" | "--output" => output = Some(parse_value(&arg, args.next())?),
+                "--listing" => listing = Some(parse_value(&arg, args.next())?),
+                _ if arg.starts_with('-') => return Err(format!("unknown option '{}'", arg)),
+                _ if source.is_some() => return Err(format!("unexpected argument '{}'", arg)),
+                _ => source = Some(PathBuf::from(arg)),
+            }
+        }
+
+        let source: PathBuf = source.ok_or_else(|| "missing source path".to_owned())?;
+        Ok(Command::Asm {
+            output: output.unwrap_or_else(|| source.with_extension("ch8")),
+            source,
+            listing,
+        })
+    }
+}
+
+fn parse_platform(value: Option<String>) -> Result<Platform, String> {
+    let value = value.ok_or_else(|| "--platform requires a value".to_owned())?;
+    Platform::from_name(&value).ok_or_else(|| {
+        format!(
+            "unknown platform '{}' (expected one of {})",
+            value,
+            Platform::NAMES.join(", ")
+        )
+    })
+}
+
+fn parse_quirks(value: Option<String>) -> Result<Quirks, String> {
+    let value = value.ok_or_else(|| "--quirks requires a value".to_owned())?;
+    Quirks::from_profile(&value).ok_or_else(|| {
+        format!(
+            "unknown quirk profile '{}' (expected one of {})",
+            value,
+            Quirks::PROFILES.join(", ")
+        )
+    })
+}
+
+fn parse_range(value: Option<String>) -> Result<RangeInclusive<u16>, String> {
+    let value = value.ok_or_else(|| "--trace-range requires a value".to_owned())?;
+    let parse = |address: &str| u16::from_str_radix(address.trim_start_matches("0x"), 16).ok();
+    match value.split_once('-') {
+        Some((start, end)) => match (parse(start), parse(end)) {
+            (Some(start), Some(end)) if start <= end => Ok(start..=end),
+            _ => Err(format!("invalid value '{}' for --trace-range", value)),
+        },
+        None => Err(format!(
+            "invalid value '{}' for --trace-range (expected start-end)",
+            value
+        )),
+    }
+}
+
+fn parse_value<T: std::str::FromStr>(option: &str, value: Option<String>) -> Result<T, String> {
+    let value = value.ok_or_else(|| format!("{} requires a value", option))?;
+    value
+        .parse()
+        .map_err(|_| format!("invalid value '{}' for {}", value, option))
+}
@@ -0,0 +1,210 @@
+mod watch;
+
+pub use watch::{Comparison, Condition, Register, WatchKind, Watchpoint};
+
+use crate::chip8::{Access, Chip8Vm, Instruction, VmError};
+use crate::pacer::Pacer;
+
+use std::collections::BTreeSet;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Stop {
+    Step,
+    Breakpoint(u16),
+    Watchpoint {
+        pc: u16,
+        instruction: Instruction,
+        access: Access,
+    },
+    Condition {
+        pc: u16,
+        instruction: Instruction,
+        condition: Condition,
+    },
+    Exited,
+    Quit,
+}
+
+pub struct Debugger {
+    vm: Chip8Vm,
+    cycles_per_frame: u32,
+    cycle: u64,
+    breakpoints: BTreeSet<u16>,
+    watchpoints: Vec<Watchpoint>,
+    conditions: Vec<(Condition, bool)>,
+    pacer: Option<Pacer>,
+}
+
+impl Debugger {
+    pub fn new(mut vm: Chip8Vm, cycles_per_frame: u32) -> Self {
+        vm.begin_frame();
+        Self {
+            vm,
+            cycles_per_frame: cycles_per_frame.max(1),
+            cycle: 0,
+            breakpoints: BTreeSet::new(),
+            watchpoints: Vec::new(),
+            conditions: Vec::new(),
+            pacer: None,
+        }
+    }
+
+    pub fn set_pacer(&mut self, pacer: Option<Pacer>) {
+        self.pacer = pacer;
+    }
+
+    pub fn vm(&self) -> &Chip8Vm {
+        &self.vm
+    }
+
+    pub fn vm_mut(&mut self) -> &mut Chip8Vm {
+        &mut self.vm
+    }
+
+    pub fn cycle(&self) -> u64 {
+        self.cycle
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = u16> + '_ {
+        self.breakpoints.iter().copied()
+    }
+
+    pub fn add_breakpoint(&mut self, address: u16) -> bool {
+        self.breakpoints.insert(address)
+    }
+
+    pub fn remove_breakpoint(&mut self, address: u16) -> bool {
+        self.breakpoints.remove(&address)
+    }
+
+    pub fn watchpoints(&self) -> &[Watchpoint] {
+        &self.watchpoints
+    }
+
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) {
+        self.watchpoints.push(watchpoint);
+        self.vm.set_access_tracking(true);
+    }
+
+    pub fn remove_watchpoint(&mut self, index: usize) -> Option<Watchpoint> {
+        if index >= self.watchpoints.len() {
+            return None;
+        }
+        let watchpoint = self.watchpoints.remove(index);
+        self.vm.set_access_tracking(!self.watchpoints.is_empty());
+        Some(watchpoint)
+    }
+
+    pub fn conditions(&self) -> impl Iterator<Item = &Condition> {
+        self.conditions.iter().map(|(condition, _)| condition)
+    }
+
+    pub fn add_condition(&mut self, condition: Condition) {
+        let holds = condition.check(&self.vm);
+        self.conditions.push((condition, holds));
+    }
+
+    pub fn remove_condition(&mut self, index: usize) -> Option<Condition> {
+        if index >= self.conditions.len() {
+            return None;
+        }
+        Some(self.conditions.remove(index).0)
+    }
+
+    pub fn step(&mut self, count: u64) -> Result<Stop, VmError> {
+        let mut remaining = count;
+        self.run_until(|_| {
+            remaining = remaining.saturating_sub(1);
+            remaining == 0
+        })
+    }
+
+    pub fn step_over(&mut self) -> Result<Stop, VmError> {
+        match self.vm.next_instruction()? {
+            Instruction::Call(_) => {
+                let depth = self.vm.stack().len();
+                self.run_until(|vm| vm.stack().len() == depth)
+            }
+            _ => self.step(1),
+        }
+    }
+
+    pub fn step_out(&mut self) -> Result<Stop, VmError> {
+        let depth = self.vm.stack().len();
+        self.run_until(|vm| vm.stack().len() < depth)
+    }
+
+    pub fn resume(&mut self) -> Result<Stop, VmError> {
+        self.run_until(|_| false)
+    }
+
+    fn run_until(&mut self, mut done: impl FnMut(&Chip8Vm) -> bool) -> Result<Stop, VmError> {
+        loop {
+            if self.vm.has_exited() {
+                return Ok(Stop::Exited);
+            }
+            if self.vm.should_quit() {
+                return Ok(Stop::Quit);
+            }
+            let pc = self.vm.pc();
+            let instruction = self.vm.next_instruction()?;
+            self.execute()?;
+            if let Some(stop) = self.check_watches(pc, instruction) {
+                return Ok(stop);
+            }
+            if self.breakpoints.contains(&self.vm.pc()) {
+                return Ok(Stop::Breakpoint(self.vm.pc()));
+            }
+            if done(&self.vm) {
+                return Ok(Stop::Step);
+            }
+        }
+    }
+
+    fn check_watches(&mut self, pc: u16, instruction: Instruction) -> Option<Stop> {
+        let access = self.vm.accesses().iter().copied().find(|access| {
+            self.watchpoints
+                .iter()
+                .any(|watchpoint| watchpoint.matches(*access))
+        });
+
+        let mut triggered = None;
+        for (condition, held) in &mut self.conditions {
+            let holds = condition.check(&self.vm);
+            if holds && !*held && triggered.is_none() {
+                triggered = Some(*condition);
+            }
+            *held = holds;
+        }
+
+        if let Some(access) = access {
+            return Some(Stop::Watchpoint {
+                pc,
+                instruction,
+                access,
+            });
+        }
+        triggered.map(|condition| Stop::Condition {
+            pc,
+            instruction,
+            condition,
+        })
+    }
+
+    fn execute(&mut self) -> Result<(), VmError> {
+        self.vm.step()?;
+        self.cycle += 1;
+        if self.cycle % self.cycles_per_frame as u64 == 0 {
+            self.vm.end_frame();
+            if let Some(pacer) = &mut self.pacer {
+                pacer.wait();
+            }
+            self.vm.begin_frame();
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,200 @@
+use super::{Comparison, Condition, Debugger, Register, Stop, WatchKind, Watchpoint};
+use crate::asm::assemble;
+use crate::chip8::{Access, Chip8Vm, Instruction, Platform, Quirks};
+use crate::frontend::MockFrontEnd;
+
+fn debugger(source: &str) -> Debugger {
+    let mut vm = Chip8Vm::with_platform(
+        Box::new(MockFrontEnd::default()),
+        Platform::SuperChip,
+        Quirks::schip(),
+    );
+    vm.load_rom_bytes(&assemble(source).unwrap().bytes).unwrap();
+    Debugger::new(vm, 10)
+}
+
+#[test]
+fn test_breakpoints() {
+    let mut debugger = debugger(
+        "\
+loop: ADD V0, 1
+      SE V0, 5
+      JP loop
+      EXIT",
+    );
+    debugger.add_breakpoint(0x204);
+
+    assert_eq!(debugger.resume().unwrap(), Stop::Breakpoint(0x204));
+    assert_eq!(debugger.vm().v()[0], 1);
+    assert_eq!(debugger.resume().unwrap(), Stop::Breakpoint(0x204));
+    assert_eq!(debugger.vm().v()[0], 2);
+
+    assert!(debugger.remove_breakpoint(0x204));
+    debugger.vm_mut().set_v(0, 4);
+    assert_eq!(debugger.resume().unwrap(), Stop::Exited);
+    assert_eq!(debugger.vm().pc(), 0x206);
+    assert_eq!(debugger.cycle(), 9);
+}
+
+#[test]
+fn test_step_onto_breakpoint() {
+    let mut debugger = debugger(
+        "\
+      LD V0, 1
+      LD V1, 2
+      EXIT",
+    );
+    debugger.add_breakpoint(0x202);
+
+    assert_eq!(debugger.step(1).unwrap(), Stop::Breakpoint(0x202));
+    assert_eq!(debugger.step(1).unwrap(), Stop::Step);
+    assert_eq!(debugger.vm().pc(), 0x204);
+}
+
+#[test]
+fn test_step() {
+    let mut debugger = debugger(
+        "\
+      CALL outer
+      JP 0x202
+outer:
+      CALL inner
+      LD V1, 1
+      RET
+inner:
+      LD V2, 2
+      LD V3, 3
+      RET",
+    );
+
+    assert_eq!(debugger.step(2).unwrap(), Stop::Step);
+    assert_eq!(debugger.vm().pc(), 0x20A);
+    assert_eq!(debugger.vm().stack(), &[0x200, 0x204]);
+
+    assert_eq!(debugger.step_out().unwrap(), Stop::Step);
+    assert_eq!(debugger.vm().pc(), 0x206);
+    assert_eq!(debugger.vm().stack(), &[0x200]);
+    assert_eq!(debugger.vm().v()[3], 3);
+
+    debugger.vm_mut().set_pc(0x204);
+    debugger.vm_mut().set_v(3, 0);
+    debugger.add_breakpoint(0x20A);
+    assert_eq!(debugger.step_over().unwrap(), Stop::Breakpoint(0x20A));
+    assert_eq!(debugger.step_over().unwrap(), Stop::Step);
+    assert_eq!(debugger.vm().pc(), 0x20C);
+    assert_eq!(debugger.step_out().unwrap(), Stop::Step);
+    assert_eq!(debugger.vm().pc(), 0x206);
+    assert_eq!(debugger.vm().v()[3], 3);
+
+    debugger.vm_mut().set_pc(0x204);
+    debugger.remove_breakpoint(0x20A);
+    assert_eq!(debugger.step_over().unwrap(), Stop::Step);
+    assert_eq!(debugger.vm().pc(), 0x206);
+    assert_eq!(debugger.vm().stack(), &[0x200]);
+
+    debugger.vm_mut().memory_mut()[0x206] = 0x00;
+    debugger.vm_mut().memory_mut()[0x207] = 0xFD;
+    assert_eq!(debugger.step_over().unwrap(), Stop::Step);
+    assert_eq!(debugger.step(1).unwrap(), Stop::Exited);
+}
+
+#[test]
+fn test_fault() {
+    let mut debugger = debugger("RET");
+
+    assert!(debugger.step(1).is_err());
+    assert_eq!(debugger.vm().pc(), 0x200);
+    assert_eq!(debugger.cycle(), 0);
+}
+
+#[test]
+fn test_watchpoints() {
+    let mut debugger = debugger(
+        "\
+      LD V0, 123
+      LD I, digits
+      LD B, V0
+      LD V2, [I]
+      LD I, 0x200
+      LD [I], V1
+digits:
+      db 0, 0, 0",
+    );
+    debugger.add_watchpoint(Watchpoint::new(0x20D..=0x20D, WatchKind::Write));
+    debugger.add_watchpoint(Watchpoint::new(0x200..=0x201, WatchKind::Write));
+
+    assert_eq!(
+        debugger.resume().unwrap(),
+        Stop::Watchpoint {
+            pc: 0x204,
+            instruction: Instruction::Bcd(0),
+            access: Access::Write(0x20D),
+        }
+    );
+    assert_eq!(debugger.vm().memory()[0x20C..0x20F], [1, 2, 3]);
+
+    debugger.add_watchpoint(Watchpoint::new(0x20C..=0x20E, WatchKind::Read));
+    assert_eq!(
+        debugger.resume().unwrap(),
+        Stop::Watchpoint {
+            pc: 0x206,
+            instruction: Instruction::Read(2),
+            access: Access::Read(0x20C),
+        }
+    );
+
+    assert!(debugger.remove_watchpoint(2).is_some());
+    assert!(debugger.remove_watchpoint(2).is_none());
+    assert_eq!(
+        debugger.resume().unwrap(),
+        Stop::Watchpoint {
+            pc: 0x20A,
+            instruction: Instruction::Store(1),
+            access: Access::Write(0x200),
+        }
+    );
+}
+
+#[test]
+fn test_conditions() {
+    let mut debugger = debugger(
+        "\
+loop: ADD V3, 8
+      LD I, 0xDF0
+      ADD I, V3
+      JP loop",
+    );
+    debugger.add_condition(Condition::parse("V3 == 0x10").unwrap());
+    debugger.add_condition(Condition::parse("I>E00").unwrap());
+
+    let condition = Condition {
+        register: Register::V(3),
+        comparison: Comparison::Equal,
+        value: 0x10,
+    };
+    assert_eq!(
+        debugger.resume().unwrap(),
+        Stop::Condition {
+            pc: 0x200,
+            instruction: Instruction::AddByte { x: 3, byte: 8 },
+            condition,
+        }
+    );
+    assert_eq!(debugger.cycle(), 5);
+
+    let stop = debugger.resume().unwrap();
+    assert_eq!(debugger.vm().i(), 0xE08);
+    assert_eq!(debugger.vm().v()[3], 0x18);
+    match stop {
+        Stop::Condition { pc, condition, .. } => {
+            assert_eq!(pc, 0x204);
+            assert_eq!(condition.to_string(), "I > 0xE00");
+        }
+        stop => panic!("unexpected stop {:?}", stop),
+    }
+
+    assert_eq!(debugger.remove_condition(1), Condition::parse("I > 0xe00"));
+    assert!(Condition::parse("V3 = 1").is_none());
+    assert!(Condition::parse("VG == 1").is_none());
+    assert!(Condition::parse("DT <").is_none());
+}
@@ -0,0 +1,202 @@
+use crate::chip8::{Access, Chip8Vm};
+
+use std::fmt;
+use std::ops::RangeInclusive;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Register {
+    V(usize),
+    I,
+    Pc,
+    Sp,
+    Dt,
+    St,
+}
+
+impl Register {
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.to_ascii_uppercase();
+        match name.as_str() {
+            "I" => Some(Register::I),
+            "PC" => Some(Register::Pc),
+            "SP" => Some(Register::Sp),
+            "DT" => Some(Register::Dt),
+            "ST" => Some(Register::St),
+            _ if name.len() == 2 && name.starts_with('V') => {
+                usize::from_str_radix(&name[1..], 16).ok().map(Register::V)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn get(self, vm: &Chip8Vm) -> u16 {
+        match self {
+            Register::V(x) => vm.v()[x & 0xF] as u16,
+            Register::I => vm.i(),
+            Register::Pc => vm.pc(),
+            Register::Sp => vm.stack().len() as u16,
+            Register::Dt => vm.delay_timer() as u16,
+            Register::St => vm.sound_timer() as u16,
+        }
+    }
+
+    pub fn set(self, vm: &mut Chip8Vm, value: u16) -> bool {
+        let byte = value as u8;
+        match self {
+            Register::I => vm.set_i(value),
+            Register::Pc => vm.set_pc(value),
+            Register::Sp => return false,
+            _ if value > 0xFF => return false,
+            Register::V(x) => vm.set_v(x, byte),
+            Register::Dt => vm.set_delay_timer(byte),
+            Register::St => vm.set_sound_timer(byte),
+        }
+        true
+    }
+}
+
+impl fmt::Display for Register {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Register::V(x) => write!(f, "V{:X}", x),
+            Register::I => write!(f, "I"),
+            Register::Pc => write!(f, "PC"),
+            Register::Sp => write!(f, "SP"),
+            Register::Dt => write!(f, "DT"),
+            Register::St => write!(f, "ST"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+impl Comparison {
+    const OPERATORS: [(&'static str, Comparison); 6] = [
+        ("==", Comparison::Equal),
+        ("!=", Comparison::NotEqual),
+        ("<=", Comparison::LessOrEqual),
+        (">=", Comparison::GreaterOrEqual),
+        ("<", Comparison::Less),
+        (">", Comparison::Greater),
+    ];
+
+    fn apply(self, left: u16, right: u16) -> bool {
+        match self {
+            Comparison::Equal => left == right,
+            Comparison::NotEqual => left != right,
+            Comparison::Less => left < right,
+            Comparison::LessOrEqual => left <= right,
+            Comparison::Greater => left > right,
+            Comparison::GreaterOrEqual => left >= right,
+        }
+    }
+
+    fn operator(self) -> &'static str {
+        Self::OPERATORS
+            .iter()
+            .find(|(_, comparison)| *comparison == self)
+            .map(|(operator, _)| *operator)
+            .unwrap()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Condition {
+    pub register: Register,
+    pub comparison: Comparison,
+    pub value: u16,
+}
+
+impl Condition {
+    pub fn parse(text: &str) -> Option<Self> {
+        let start = text.find(|c| "=!<>".contains(c))?;
+        let (register, rest) = text.split_at(start);
+        let (operator, comparison) = Comparison::OPERATORS
+            .iter()
+            .find(|(operator, _)| rest.starts_with(operator))?;
+        let value = rest[operator.len()..].trim();
+        let value = value
+            .strip_prefix("0x")
+            .or_else(|| value.strip_prefix("0X"))
+            .unwrap_or(value);
+
+        Some(Self {
+            register: Register::from_name(register.trim())?,
+            comparison: *comparison,
+            value: u16::from_str_radix(value, 16).ok()?,
+        })
+    }
+
+    pub fn check(&self, vm: &Chip8Vm) -> bool {
+        self.comparison.apply(self.register.get(vm), self.value)
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} 0x{:X}",
+            self.register,
+            self.comparison.operator(),
+            self.value
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WatchKind {
+    Read,
+    Write,
+    Access,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Watchpoint {
+    pub addresses: RangeInclusive<u16>,
+    pub kind: WatchKind,
+}
+
+impl Watchpoint {
+    pub fn new(addresses: RangeInclusive<u16>, kind: WatchKind) -> Self {
+        Self { addresses, kind }
+    }
+
+    pub fn matches(&self, access: Access) -> bool {
+        let kind = match access {
+            Access::Read(_) => WatchKind::Read,
+            Access::Write(_) => WatchKind::Write,
+        };
+        (self.kind == WatchKind::Access || self.kind == kind)
+            && self.addresses.contains(&(access.address() as u16))
+    }
+}
+
+impl fmt::Display for Watchpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = match self.kind {
+            WatchKind::Read => "read",
+            WatchKind::Write => "write",
+            WatchKind::Access => "access",
+        };
+        if self.addresses.start() == self.addresses.end() {
+            write!(f, "{} {:03X}", kind, self.addresses.start())
+        } else {
+            write!(
+                f,
+                "{} {:03X}-{:03X}",
+                kind,
+                self.addresses.start(),
+                self.addresses.end()
+            )
+        }
+    }
+}
@@ -0,0 +1,139 @@
+pub use crate::chip8::{Fetched, Instruction};
+use crate::chip8::{Platform, HIRES_CHIP8_ENTRY, HIRES_CHIP8_JUMP};
+
+use std::collections::BTreeSet;
+use std::fmt;
+
+const BYTES_PER_DATA_LINE: usize = 8;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum LineKind {
+    Code(Fetched),
+    Data,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Line {
+    pub address: usize,
+    pub bytes: Vec<u8>,
+    pub kind: LineKind,
+}
+
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:03X}  ", self.address)?;
+        match self.kind {
+            LineKind::Code(fetched) => match fetched.operand {
+                Some(operand) => write!(f, "{:04X} {:04X}  {}", fetched.opcode, operand, fetched),
+                None => write!(f, "{:04X}       {}", fetched.opcode, fetched),
+            },
+            LineKind::Data => {
+                let bytes: Vec<String> = self
+                    .bytes
+                    .iter()
+                    .map(|byte| format!("0x{:02X}", byte))
+                    .collect();
+                write!(f, "           db {}", bytes.join(", "))
+            }
+        }
+    }
+}
+
+pub fn disassemble(rom: &[u8], platform: Platform) -> Vec<Line> {
+    let start = platform.program_start() as usize;
+    let code = find_code(rom, platform);
+
+    let mut lines = Vec::new();
+    let mut offset = 0;
+    while offset < rom.len() {
+        let address = start + offset;
+        let fetched = if code.contains(&address) {
+            fetch_at(rom, offset, platform)
+        } else {
+            None
+        };
+        if let Some(fetched) = fetched {
+            let size = fetched.size();
+            lines.push(Line {
+                address,
+                bytes: rom[offset..offset + size].to_vec(),
+                kind: LineKind::Code(fetched),
+            });
+            offset += size;
+            continue;
+        }
+
+        let mut end = offset + 1;
+        while end < rom.len()
+            && end - offset < BYTES_PER_DATA_LINE
+            && !code.contains(&(start + end))
+        {
+            end += 1;
+        }
+        lines.push(Line {
+            address,
+            bytes: rom[offset..end].to_vec(),
+            kind: LineKind::Data,
+        });
+        offset = end;
+    }
+
+    lines
+}
+
+fn fetch_at(rom: &[u8], offset: usize, platform: Platform) -> Option<Fetched> {
+    Fetched::read_at(rom, offset, platform).filter(|fetched| {
+        fetched
+            .instruction
+            .is_some_and(|instruction| instruction.size() == fetched.size())
+    })
+}
+
+fn decode_at(rom: &[u8], offset: usize, platform: Platform) -> Option<Instruction> {
+    fetch_at(rom, offset, platform).and_then(|fetched| fetched.instruction)
+}
+
+fn find_code(rom: &[u8], platform: Platform) -> BTreeSet<usize> {
+    let start = platform.program_start() as usize;
+    let mut code = BTreeSet::new();
+    let mut pending = vec![start];
+
+    while let Some(address) = pending.pop() {
+        if address < start || code.contains(&address) {
+            continue;
+        }
+        let instruction = match decode_at(rom, address - start, platform) {
+            Some(instruction) => instruction,
+            None => continue,
+        };
+        code.insert(address);
+
+        let next = address + instruction.size();
+        match instruction {
+            Instruction::Jump(target)
+                if platform.supports_hires_chip8()
+                    && address == start
+                    && target == HIRES_CHIP8_JUMP =>
+            {
+                pending.push(HIRES_CHIP8_ENTRY as usize)
+            }
+            Instruction::Jump(target) => pending.push(target as usize),
+            Instruction::Call(target) => {
+                pending.push(target as usize);
+                pending.push(next);
+            }
+            Instruction::Return | Instruction::Exit | Instruction::JumpV0(_) => {}
+            instruction if instruction.is_skip() => {
+                pending.push(next);
+                let skipped = decode_at(rom, next - start, platform).map_or(2, Instruction::size);
+                pending.push(next + skipped);
+            }
+            _ => pending.push(next),
+        }
+    }
+
+    code
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,68 @@
+use super::{disassemble, Fetched, Instruction, LineKind};
+use crate::chip8::Platform;
+
+#[test]
+fn test_mnemonics() {
+    let text = |opcode| {
+        Instruction::decode(opcode, Platform::XoChip)
+            .unwrap()
+            .to_string()
+    };
+
+    assert_eq!(text(0x00E0), "CLS");
+    assert_eq!(text(0x1234), "JP 0x234");
+    assert_eq!(text(0x3A0F), "SE VA, 0x0F");
+    assert_eq!(text(0x8124), "ADD V1, V2");
+    assert_eq!(text(0xB300), "JP V0, 0x300");
+    assert_eq!(text(0xD01F), "DRW V0, V1, 15");
+    assert_eq!(text(0xF265), "LD V2, [I]");
+    assert_eq!(text(0xF201), "PLANE 2");
+    assert_eq!(text(0x5232), "SAVE V2, V3");
+}
+
+#[test]
+fn test_disassemble() {
+    let rom = [
+        0xA2, 0x0A, // 200 LD I, 0x20A
+        0x3A, 0x00, // 202 SE VA, 0x00
+        0x22, 0x08, // 204 CALL 0x208
+        0x12, 0x06, // 206 JP 0x206
+        0x00, 0xEE, // 208 RET
+        0xFF, 0x81, // 20A sprite
+        0x12, // 20C
+    ];
+
+    let lines = disassemble(&rom, Platform::Chip8);
+
+    let text: Vec<String> = lines.iter().map(|line| line.to_string()).collect();
+    assert_eq!(
+        text,
+        vec![
+            "200  A20A       LD I, 0x20A",
+            "202  3A00       SE VA, 0x00",
+            "204  2208       CALL 0x208",
+            "206  1206       JP 0x206",
+            "208  00EE       RET",
+            "20A             db 0xFF, 0x81, 0x12",
+        ]
+    );
+    assert_eq!(lines[5].kind, LineKind::Data);
+}
+
+#[test]
+fn test_disassemble_long_load() {
+    let rom = [0xF0, 0x00, 0x12, 0x34, 0x12, 0x04];
+
+    let lines = disassemble(&rom, Platform::XoChip);
+
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0].to_string(), "200  F000 1234  LD I, LONG 0x1234");
+    assert_eq!(
+        lines[1].kind,
+        LineKind::Code(Fetched {
+            opcode: 0x1204,
+            operand: None,
+            instruction: Some(Instruction::Jump(0x204)),
+        })
+    );
+}
@@ -1,6 +1,12 @@
 use crate::chip8::memory::DisplayBuffer;
+use crate::chip8::{Platform, AUDIO_PATTERN_SIZE};
 
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
 use std::ops::{Index, IndexMut};
+use std::rc::Rc;
 use std::slice::SliceIndex;
 
 #[derive(Clone, Copy)]
@@ -15,6 +21,20 @@ impl Default for Keys {
     }
 }
 
+impl Keys {
+    pub fn to_bits(&self) -> u16 {
+        (0..16).fold(0, |bits, key| bits | (self[key].0 as u16) << key)
+    }
+
+    pub fn from_bits(bits: u16) -> Self {
+        let mut keys = Keys::default();
+        for key in 0..16 {
+            keys[key] = Key(bits & 1 << key != 0);
+        }
+        keys
+    }
+}
+
 impl<Idx> Index<Idx> for Keys
 where
     Idx: SliceIndex<[Key]>,
@@ -35,44 +55,177 @@ where
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct InvalidKey {
+    pub key: usize,
+}
+
+impl fmt::Display for InvalidKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "key {} is not on the 16-key keypad", self.key)
+    }
+}
+
+impl Error for InvalidKey {}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Request {
+    SaveState(usize),
+    LoadState(usize),
+    Rewind(usize),
+    TogglePause,
+    Step,
+}
+
+#[derive(Clone, Copy)]
+pub struct DebugInfo<'a> {
+    pub platform: Platform,
+    pub v: [u8; 16],
+    pub i: u16,
+    pub pc: u16,
+    pub stack: &'a [u16],
+    pub dt: u8,
+    pub st: u8,
+    pub keys: &'a Keys,
+    pub memory: &'a [u8],
+    pub paused: bool,
+}
+
 pub trait FrontEnd {
     fn draw(&mut self, data: &DisplayBuffer);
 
     fn get_keys(&mut self) -> &Keys;
 
+    fn set_buzzer(&mut self, _on: bool) {}
+
+    fn set_audio_pattern(&mut self, _pattern: &[u8; AUDIO_PATTERN_SIZE], _pitch: u8) {}
+
     fn should_quit(&self) -> bool;
 
     fn update(&mut self);
 
-    fn wait_for_keypress(&mut self) -> u8;
+    fn end_frame(&mut self) {}
+
+    fn rewind(&mut self, _frames: usize) {}
+
+    fn poll_request(&mut self) -> Option<Request> {
+        None
+    }
+
+    fn wants_debug_info(&self) -> bool {
+        false
+    }
+
+    fn draw_debug_info(&mut self, _info: &DebugInfo) {}
 }
 
+#[derive(Default)]
 pub struct MockFrontEnd {
     keys: Keys,
 }
 
-impl Default for MockFrontEnd {
-    fn default() -> Self {
-        Self {
-            keys: Keys::default(),
+impl FrontEnd for MockFrontEnd {
+    fn draw(&mut self, _data: &DisplayBuffer) {}
+
+    fn get_keys(&mut self) -> &Keys {
+        &self.keys
+    }
+
+    fn should_quit(&self) -> bool {
+        false
+    }
+
+    fn update(&mut self) {}
+}
+
+#[derive(Default)]
+struct HeadlessState {
+    screen: DisplayBuffer,
+    frames: u64,
+    script: BTreeMap<u64, Keys>,
+    buzzer: bool,
+}
+
+#[derive(Clone, Default)]
+pub struct HeadlessFrontEnd {
+    state: Rc<RefCell<HeadlessState>>,
+    keys: Keys,
+}
+
+impl HeadlessFrontEnd {
+    pub fn set_keys(&self, frame: u64, keys: Keys) {
+        self.state.borrow_mut().script.insert(frame, keys);
+    }
+
+    pub fn press_key(&self, frame: u64, key: usize) -> Result<(), InvalidKey> {
+        if key >= 16 {
+            return Err(InvalidKey { key });
         }
+        let mut state = self.state.borrow_mut();
+        let mut keys = scripted_keys(&state.script, frame);
+        let released = scripted_keys(&state.script, frame + 1);
+        keys[key] = Key(true);
+        state.script.insert(frame, keys);
+        state.script.entry(frame + 1).or_insert(released);
+        Ok(())
+    }
+
+    pub fn frames(&self) -> u64 {
+        self.state.borrow().frames
+    }
+
+    pub fn buzzer(&self) -> bool {
+        self.state.borrow().buzzer
+    }
+
+    pub fn screen(&self) -> DisplayBuffer {
+        self.state.borrow().screen.clone()
+    }
+
+    pub fn screen_text(&self) -> String {
+        self.state.borrow().screen.to_string()
+    }
+
+    pub fn screen_bytes(&self) -> Vec<u8> {
+        self.state.borrow().screen.data.clone()
     }
 }
 
-impl FrontEnd for MockFrontEnd {
-    fn draw(&mut self, _data: &DisplayBuffer) {}
+fn scripted_keys(script: &BTreeMap<u64, Keys>, frame: u64) -> Keys {
+    script
+        .range(..=frame)
+        .next_back()
+        .map(|(_, keys)| keys.clone())
+        .unwrap_or_default()
+}
+
+impl FrontEnd for HeadlessFrontEnd {
+    fn draw(&mut self, data: &DisplayBuffer) {
+        self.state.borrow_mut().screen = data.clone();
+    }
 
     fn get_keys(&mut self) -> &Keys {
+        let state = self.state.borrow();
+        self.keys = scripted_keys(&state.script, state.frames);
         &self.keys
     }
 
+    fn set_buzzer(&mut self, on: bool) {
+        self.state.borrow_mut().buzzer = on;
+    }
+
     fn should_quit(&self) -> bool {
         false
     }
 
     fn update(&mut self) {}
 
-    fn wait_for_keypress(&mut self) -> u8 {
-        0
+    fn end_frame(&mut self) {
+        self.state.borrow_mut().frames += 1;
+    }
+
+    fn rewind(&mut self, frames: usize) {
+        let mut state = self.state.borrow_mut();
+        state.frames = state.frames.saturating_sub(frames as u64);
     }
 }
@@ -0,0 +1,442 @@
+use crate::chip8::{Access, VmError};
+use crate::debugger::{Debugger, Register, Stop, WatchKind, Watchpoint};
+
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+const PACKET_SIZE: usize = 0x1000;
+const INTERRUPT_CHECK_CYCLES: u64 = 100;
+const INTERRUPT: u8 = 0x03;
+
+const SIGINT: u8 = 2;
+const SIGILL: u8 = 4;
+const SIGTRAP: u8 = 5;
+const SIGSEGV: u8 = 11;
+
+const REGISTERS: [(Register, &str, usize); 21] = [
+    (Register::V(0x0), "v0", 1),
+    (Register::V(0x1), "v1", 1),
+    (Register::V(0x2), "v2", 1),
+    (Register::V(0x3), "v3", 1),
+    (Register::V(0x4), "v4", 1),
+    (Register::V(0x5), "v5", 1),
+    (Register::V(0x6), "v6", 1),
+    (Register::V(0x7), "v7", 1),
+    (Register::V(0x8), "v8", 1),
+    (Register::V(0x9), "v9", 1),
+    (Register::V(0xA), "va", 1),
+    (Register::V(0xB), "vb", 1),
+    (Register::V(0xC), "vc", 1),
+    (Register::V(0xD), "vd", 1),
+    (Register::V(0xE), "ve", 1),
+    (Register::V(0xF), "vf", 1),
+    (Register::I, "i", 2),
+    (Register::Pc, "pc", 2),
+    (Register::Sp, "sp", 1),
+    (Register::Dt, "dt", 1),
+    (Register::St, "st", 1),
+];
+
+pub fn serve(debugger: &mut Debugger, listener: &TcpListener) -> io::Result<()> {
+    let (stream, _) = listener.accept()?;
+    stream.set_nodelay(true)?;
+    Session::new(debugger, stream).run()
+}
+
+pub fn target_xml() -> String {
+    let mut xml = String::from(
+        "<?xml version=\"1.0\"?>\n\
+         <!DOCTYPE target SYSTEM \"gdb-target.dtd\">\n\
+         <target version=\"1.0\">\n  <feature name=\"org.yaci.chip8\">\n",
+    );
+    for (register, name, size) in &REGISTERS {
+        let kind = match register {
+            Register::I => "data_ptr",
+            Register::Pc => "code_ptr",
+            _ => "uint8",
+        };
+        xml += &format!(
+            "    <reg name=\"{}\" bitsize=\"{}\" type=\"{}\"/>\n",
+            name,
+            size * 8,
+            kind
+        );
+    }
+    xml + "  </feature>\n</target>\n"
+}
+
+enum Flow {
+    Reply(String),
+    Close(Option<String>),
+}
+
+struct Session<'a> {
+    debugger: &'a mut Debugger,
+    stream: TcpStream,
+    last_stop: String,
+}
+
+impl<'a> Session<'a> {
+    fn new(debugger: &'a mut Debugger, stream: TcpStream) -> Self {
+        Self {
+            debugger,
+            stream,
+            last_stop: format!("S{:02x}", SIGTRAP),
+        }
+    }
+
+    fn run(&mut self) -> io::Result<()> {
+        while let Some(packet) = self.read_packet()? {
+            match self.handle(&packet)? {
+                Flow::Reply(reply) => self.write_packet(reply.as_bytes())?,
+                Flow::Close(reply) => {
+                    if let Some(reply) = reply {
+                        self.write_packet(reply.as_bytes())?;
+                    }
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn read_byte(&mut self) -> io::Result<Option<u8>> {
+        let mut byte = [0];
+        match self.stream.read(&mut byte)? {
+            0 => Ok(None),
+            _ => Ok(Some(byte[0])),
+        }
+    }
+
+    fn read_packet(&mut self) -> io::Result<Option<String>> {
+        loop {
+            match self.read_byte()? {
+                None => return Ok(None),
+                Some(b'$') => {}
+                Some(_) => continue,
+            }
+
+            let mut data = Vec::new();
+            loop {
+                match self.read_byte()? {
+                    None => return Ok(None),
+                    Some(b'#') => break,
+                    Some(byte) => data.push(byte),
+                }
+            }
+            let mut checksum = [0; 2];
+            self.stream.read_exact(&mut checksum)?;
+            let expected = std::str::from_utf8(&checksum)
+                .ok()
+                .and_then(|checksum| u8::from_str_radix(checksum, 16).ok());
+            if expected != Some(checksum_of(&data)) {
+                self.stream.write_all(b"-")?;
+                continue;
+            }
+            self.stream.write_all(b"+")?;
+            return Ok(Some(String::from_utf8_lossy(&data).into_owned()));
+        }
+    }
+
+    fn write_packet(&mut self, data: &[u8]) -> io::Result<()> {
+        let mut escaped = Vec::with_capacity(data.len());
+        for &byte in data {
+            if b"#$}*".contains(&byte) {
+                escaped.push(b'}');
+                escaped.push(byte ^ 0x20);
+            } else {
+                escaped.push(byte);
+            }
+        }
+        let mut packet = vec![b'$'];
+        packet.extend_from_slice(&escaped);
+        packet.extend_from_slice(format!("#{:02x}", checksum_of(&escaped)).as_bytes());
+        self.stream.write_all(&packet)?;
+        self.stream.flush()
+    }
+
+    fn handle(&mut self, packet: &str) -> io::Result<Flow> {
+        let mut chars = packet.chars();
+        let command = chars.next();
+        let args = chars.as_str();
+        let reply = match command {
+            Some('?') => self.last_stop.clone(),
+            Some('g') => self.read_registers(),
+            Some('G') => self.write_registers(args),
+            Some('p') => self.read_register(args),
+            Some('P') => self.write_register(args),
+            Some('m') => self.read_memory(args),
+            Some('M') => self.write_memory(args),
+            Some('c') => {
+                if !args.is_empty() && !self.set_pc(args) {
+                    return Ok(Flow::Reply(error(1)));
+                }
+                let reply = self.resume()?;
+                return Ok(self.stop(reply));
+            }
+            Some('s') => {
+                if !args.is_empty() && !self.set_pc(args) {
+                    return Ok(Flow::Reply(error(1)));
+                }
+                let reply = stop_reply(self.debugger.step(1));
+                return Ok(self.stop(reply));
+            }
+            Some('Z') => self.breakpoint(true, args),
+            Some('z') => self.breakpoint(false, args),
+            Some('q') => self.query(args),
+            Some('H') => "OK".to_owned(),
+            Some('k') => return Ok(Flow::Close(None)),
+            Some('D') => return Ok(Flow::Close(Some("OK".to_owned()))),
+            _ => String::new(),
+        };
+        Ok(Flow::Reply(reply))
+    }
+
+    fn stop(&mut self, reply: String) -> Flow {
+        self.last_stop = reply.clone();
+        Flow::Reply(reply)
+    }
+
+    fn resume(&mut self) -> io::Result<String> {
+        loop {
+            match self.debugger.step(INTERRUPT_CHECK_CYCLES) {
+                Ok(Stop::Step) => {}
+                stop => return Ok(stop_reply(stop)),
+            }
+            if self.interrupted()? {
+                return Ok(format!("S{:02x}", SIGINT));
+            }
+        }
+    }
+
+    fn interrupted(&mut self) -> io::Result<bool> {
+        let mut byte = [0];
+        self.stream.set_nonblocking(true)?;
+        let result = self.stream.read(&mut byte);
+        self.stream.set_nonblocking(false)?;
+        match result {
+            Ok(0) => Err(ErrorKind::UnexpectedEof.into()),
+            Ok(_) => Ok(byte[0] == INTERRUPT),
+            Err(error) if error.kind() == ErrorKind::WouldBlock => Ok(false),
+            Err(error) => Err(error),
+        }
+    }
+
+    fn set_pc(&mut self, address: &str) -> bool {
+        match u16::from_str_radix(address, 16) {
+            Ok(pc) => {
+                self.debugger.vm_mut().set_pc(pc);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    fn read_registers(&self) -> String {
+        REGISTERS
+            .iter()
+            .map(|(register, _, size)| self.register_hex(*register, *size))
+            .collect()
+    }
+
+    fn write_registers(&mut self, data: &str) -> String {
+        let mut offset = 0;
+        for (register, _, size) in &REGISTERS {
+            let value = match data.get(offset..offset + size * 2) {
+                Some(value) => value,
+                None => return error(1),
+            };
+            offset += size * 2;
+            if value == self.register_hex(*register, *size) {
+                continue;
+            }
+            match u16::from_str_radix(value, 16) {
+                Ok(value) if register.set(self.debugger.vm_mut(), value) => {}
+                _ => return error(2),
+            }
+        }
+        "OK".to_owned()
+    }
+
+    fn read_register(&self, number: &str) -> String {
+        match register_at(number) {
+            Some((register, size)) => self.register_hex(register, size),
+            None => error(1),
+        }
+    }
+
+    fn write_register(&mut self, args: &str) -> String {
+        let (number, value) = match args.split_once('=') {
+            Some(parts) => parts,
+            None => return error(1),
+        };
+        match (register_at(number), u16::from_str_radix(value, 16)) {
+            (Some((register, _)), Ok(value)) if register.set(self.debugger.vm_mut(), value) => {
+                "OK".to_owned()
+            }
+            _ => error(2),
+        }
+    }
+
+    fn register_hex(&self, register: Register, size: usize) -> String {
+        let value = register.get(self.debugger.vm());
+        match size {
+            2 => format!("{:04x}", value),
+            _ => format!("{:02x}", value),
+        }
+    }
+
+    fn read_memory(&self, args: &str) -> String {
+        let memory = self.debugger.vm().memory();
+        match parse_range(args) {
+            Some((address, length)) if address < memory.len() => {
+                let length = length.min(PACKET_SIZE / 2);
+                let end = address.saturating_add(length).min(memory.len());
+                memory[address..end]
+                    .iter()
+                    .map(|byte| format!("{:02x}", byte))
+                    .collect()
+            }
+            _ => error(1),
+        }
+    }
+
+    fn write_memory(&mut self, args: &str) -> String {
+        let (range, data) = match args.split_once(':') {
+            Some(parts) => parts,
+            None => return error(1),
+        };
+        let (address, bytes) = match (parse_range(range), decode_hex(data)) {
+            (Some((address, length)), Some(bytes)) if bytes.len() == length => (address, bytes),
+            _ => return error(1),
+        };
+        let memory = self.debugger.vm_mut().memory_mut();
+        if address.saturating_add(bytes.len()) > memory.len() {
+            return error(2);
+        }
+        memory[address..address + bytes.len()].copy_from_slice(&bytes);
+        "OK".to_owned()
+    }
+
+    fn breakpoint(&mut self, insert: bool, args: &str) -> String {
+        let mut parts = args.split(',');
+        let (kind, address, length) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(kind), Some(address), Some(length)) => (kind, address, length),
+            _ => return error(1),
+        };
+        let (address, length) = match (
+            u16::from_str_radix(address, 16),
+            u16::from_str_radix(length, 16),
+        ) {
+            (Ok(address), Ok(length)) => (address, length.max(1)),
+            _ => return error(1),
+        };
+        let kind = match kind {
+            "0" | "1" => {
+                if insert {
+                    self.debugger.add_breakpoint(address);
+                } else {
+                    self.debugger.remove_breakpoint(address);
+                }
+                return "OK".to_owned();
+            }
+            "2" => WatchKind::Write,
+            "3" => WatchKind::Read,
+            "4" => WatchKind::Access,
+            _ => return String::new(),
+        };
+
+        let watchpoint = Watchpoint::new(address..=address.saturating_add(length - 1), kind);
+        if insert {
+            self.debugger.add_watchpoint(watchpoint);
+        } else if let Some(index) = self
+            .debugger
+            .watchpoints()
+            .iter()
+            .position(|existing| *existing == watchpoint)
+        {
+            self.debugger.remove_watchpoint(index);
+        }
+        "OK".to_owned()
+    }
+
+    fn query(&self, query: &str) -> String {
+        if query.starts_with("Supported") {
+            return format!("PacketSize={:x};qXfer:features:read+", PACKET_SIZE);
+        }
+        if let Some(range) = query.strip_prefix("Xfer:features:read:target.xml:") {
+            let xml = target_xml();
+            return match parse_range(range) {
+                Some((offset, _)) if offset >= xml.len() => "l".to_owned(),
+                Some((offset, length)) => {
+                    let end = offset.saturating_add(length.min(PACKET_SIZE - 1));
+                    if end < xml.len() {
+                        format!("m{}", &xml[offset..end])
+                    } else {
+                        format!("l{}", &xml[offset..])
+                    }
+                }
+                None => error(1),
+            };
+        }
+        match query {
+            "Attached" => "1".to_owned(),
+            "C" => "QC1".to_owned(),
+            "fThreadInfo" => "m1".to_owned(),
+            "sThreadInfo" => "l".to_owned(),
+            _ => String::new(),
+        }
+    }
+}
+
+fn stop_reply(stop: Result<Stop, VmError>) -> String {
+    match stop {
+        Ok(Stop::Watchpoint { access, .. }) => {
+            let (kind, address) = match access {
+                Access::Read(address) => ("rwatch", address),
+                Access::Write(address) => ("watch", address),
+            };
+            format!("T{:02x}{}:{:x};", SIGTRAP, kind, address)
+        }
+        Ok(Stop::Exited) | Ok(Stop::Quit) => "W00".to_owned(),
+        Ok(_) => format!("S{:02x}", SIGTRAP),
+        Err(VmError::UnknownOpcode { .. }) => format!("S{:02x}", SIGILL),
+        Err(_) => format!("S{:02x}", SIGSEGV),
+    }
+}
+
+fn register_at(number: &str) -> Option<(Register, usize)> {
+    let number = usize::from_str_radix(number, 16).ok()?;
+    REGISTERS
+        .get(number)
+        .map(|(register, _, size)| (*register, *size))
+}
+
+fn parse_range(args: &str) -> Option<(usize, usize)> {
+    let (address, length) = args.split_once(',')?;
+    Some((
+        usize::from_str_radix(address, 16).ok()?,
+        usize::from_str_radix(length, 16).ok()?,
+    ))
+}
+
+fn decode_hex(data: &str) -> Option<Vec<u8>> {
+    if data.len() % 2 != 0 {
+        return None;
+    }
+    (0..data.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(data.get(index..index + 2)?, 16).ok())
+        .collect()
+}
+
+fn checksum_of(data: &[u8]) -> u8 {
+    data.iter().fold(0, |sum: u8, byte| sum.wrapping_add(*byte))
+}
+
+fn error(code: u8) -> String {
+    format!("E{:02x}", code)
+}
+
+#[cfg(test)]
+mod tests;
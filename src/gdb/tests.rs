@@ -0,0 +1,137 @@
+use super::{checksum_of, serve, target_xml};
+use crate::asm::assemble;
+use crate::chip8::{Chip8Vm, Platform, Quirks};
+use crate::debugger::Debugger;
+use crate::frontend::MockFrontEnd;
+
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+
+struct Client(TcpStream);
+
+impl Client {
+    fn send(&mut self, packet: &str) -> String {
+        self.write_packet(packet);
+        self.receive()
+    }
+
+    fn write_packet(&mut self, packet: &str) {
+        write!(self.0, "${}#{:02x}", packet, checksum_of(packet.as_bytes())).unwrap();
+        assert_eq!(self.read_byte(), b'+');
+    }
+
+    fn receive(&mut self) -> String {
+        while self.read_byte() != b'$' {}
+        let mut reply = Vec::new();
+        loop {
+            match self.read_byte() {
+                b'#' => break,
+                byte => reply.push(byte),
+            }
+        }
+        let checksum = [self.read_byte(), self.read_byte()];
+        let checksum = u8::from_str_radix(std::str::from_utf8(&checksum).unwrap(), 16).unwrap();
+        assert_eq!(checksum, checksum_of(&reply));
+        self.0.write_all(b"+").unwrap();
+        String::from_utf8(reply).unwrap()
+    }
+
+    fn read_byte(&mut self) -> u8 {
+        let mut byte = [0];
+        self.0.read_exact(&mut byte).unwrap();
+        byte[0]
+    }
+}
+
+#[test]
+fn test_session() {
+    let rom = assemble(
+        "\
+      LD V1, 1
+loop: LD I, data
+      ADD V0, V1
+      LD [I], V0
+      JP loop
+data: db 0",
+    )
+    .unwrap();
+    let mut vm = Chip8Vm::with_platform(
+        Box::new(MockFrontEnd::default()),
+        Platform::Chip8,
+        Quirks::modern(),
+    );
+    vm.load_rom_bytes(&rom.bytes).unwrap();
+    let mut debugger = Debugger::new(vm, 10);
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+
+    let client = thread::spawn(move || {
+        let mut client = Client(TcpStream::connect(address).unwrap());
+        let mut replies = Vec::new();
+        for packet in &[
+            "qSupported:swbreak+",
+            "qXfer:features:read:target.xml:0,ffff",
+            "?",
+            "g",
+            "m200,4",
+            "mffe,ffffffffffffffff",
+            "Mffffffffffffffff,1:00",
+            "qXfer:features:read:target.xml:1,ffffffffffffffff",
+            "Z0,204,2",
+            "c",
+            "p11",
+            "P0=7f",
+            "p0",
+            "M300,2:abcd",
+            "m300,2",
+            "z0,204,2",
+            "Z2,20a,1",
+            "c",
+            "m20a,1",
+            "z2,20a,1",
+            "s",
+            "p11",
+        ] {
+            replies.push(client.send(packet));
+        }
+        client.write_packet("c");
+        client.0.write_all(&[0x03]).unwrap();
+        replies.push(client.receive());
+        replies.push(client.send("D"));
+        replies
+    });
+    serve(&mut debugger, &listener).unwrap();
+    let replies = client.join().unwrap();
+
+    assert_eq!(
+        replies,
+        vec![
+            "PacketSize=1000;qXfer:features:read+".to_owned(),
+            format!("l{}", target_xml()),
+            "S05".to_owned(),
+            format!("{}00000200000000", "00".repeat(16)),
+            "6101a20a".to_owned(),
+            "0000".to_owned(),
+            "E02".to_owned(),
+            format!("l{}", &target_xml()[1..]),
+            "OK".to_owned(),
+            "S05".to_owned(),
+            "0204".to_owned(),
+            "OK".to_owned(),
+            "7f".to_owned(),
+            "OK".to_owned(),
+            "abcd".to_owned(),
+            "OK".to_owned(),
+            "OK".to_owned(),
+            "T05watch:20a;".to_owned(),
+            "80".to_owned(),
+            "OK".to_owned(),
+            "S05".to_owned(),
+            "0202".to_owned(),
+            "S02".to_owned(),
+            "OK".to_owned(),
+        ]
+    );
+    assert!(target_xml().contains("<reg name=\"pc\" bitsize=\"16\" type=\"code_ptr\"/>"));
+}
@@ -1,6 +1,14 @@
+pub mod asm;
 pub mod bitwise;
 pub mod chip8;
+pub mod debugger;
+pub mod disasm;
 pub mod frontend;
+pub mod gdb;
+pub mod movie;
+pub mod overlay;
+pub mod pacer;
+pub mod screenshot;
 pub mod sdl2;
 
 pub use chip8::Chip8Vm;
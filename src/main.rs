@@ -1,21 +1,358 @@
-use yaci::chip8::Chip8Vm;
+mod cli;
+mod repl;
+
+use cli::{Command, Options, USAGE};
+use yaci::asm::assemble_file;
+use yaci::chip8::{Chip8Vm, Platform, Tracer, FRAME_HZ};
+use yaci::debugger::Debugger;
+use yaci::disasm::disassemble;
+use yaci::frontend::{FrontEnd, HeadlessFrontEnd, Request};
+use yaci::gdb;
+use yaci::movie::{Movie, ReplayFrontEnd};
+use yaci::pacer::Pacer;
+use yaci::screenshot::Screenshot;
 use yaci::sdl2::Sdl2FrontEnd;
 
-fn main() -> Result<(), &'static str> {
-    let mut vm = Chip8Vm::new(Box::new(Sdl2FrontEnd::new()));
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::{env, fs, process};
 
-    if vm
-        .load_game("games/Brix [Andreas Gustafsson, 1990].ch8")
-        .is_err()
-    {
-        return Err("Failed to load game.");
+const EXIT_FAULT: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+const EXIT_IO: i32 = 3;
+
+fn main() {
+    match Command::parse(env::args().skip(1)) {
+        Ok(Command::Run(options)) => process::exit(run(options)),
+        Ok(Command::Debug(options)) => process::exit(debug(options)),
+        Ok(Command::Disasm { rom, platform }) => process::exit(disasm(&rom, platform)),
+        Ok(Command::Asm {
+            source,
+            output,
+            listing,
+        }) => process::exit(asm(&source, &output, listing.as_deref())),
+        Ok(Command::Help) => println!("{}", USAGE),
+        Err(error) => {
+            eprintln!("yaci: {}\n\n{}", error, USAGE);
+            process::exit(EXIT_USAGE);
+        }
+    }
+}
+
+fn run(options: Options) -> i32 {
+    let rom = match fs::read(&options.rom) {
+        Ok(rom) => rom,
+        Err(error) => {
+            eprintln!("yaci: failed to read {}: {}", options.rom.display(), error);
+            return EXIT_USAGE;
+        }
+    };
+    let replay = match options.replay.as_ref().map(Movie::load).transpose() {
+        Ok(replay) => replay,
+        Err(error) => {
+            eprintln!("yaci: failed to read movie: {}", error);
+            return EXIT_USAGE;
+        }
+    };
+    let platform = match &replay {
+        Some(movie) => movie.platform,
+        None => options.platform.unwrap_or_else(|| Platform::detect(&rom)),
+    };
+
+    let mut frontend = open_frontend(&options);
+    let mut quirks = options.quirks.unwrap_or_else(|| platform.default_quirks());
+    let mut seed = options.seed;
+    let mut cycles_per_frame = (options.cpu_hz / FRAME_HZ).max(1);
+    if let Some(movie) = replay.clone() {
+        quirks = movie.quirks;
+        seed = Some(movie.seed);
+        cycles_per_frame = movie.cycles_per_frame;
+        frontend = Box::new(ReplayFrontEnd::new(frontend, movie));
+    }
+    let mut vm = match seed {
+        Some(seed) => Chip8Vm::with_seed(frontend, platform, quirks, seed),
+        None => Chip8Vm::with_platform(frontend, platform, quirks),
+    };
+    vm.set_stack_depth(options.stack_depth);
+
+    if let Err(error) = vm.load_rom_bytes(&rom) {
+        eprintln!("yaci: failed to load {}: {}", options.rom.display(), error);
+        return EXIT_USAGE;
+    }
+    if replay.is_some_and(|movie| movie.rom_hash != vm.rom_hash()) {
+        eprintln!("yaci: movie was recorded with a different rom");
+        return EXIT_USAGE;
+    }
+
+    vm.set_rewind_frames(options.rewind_frames);
+    if !attach_tracer(&mut vm, &options) {
+        return EXIT_IO;
+    }
+
+    if platform.rpl_flags() > 0 && options.record.is_none() && options.replay.is_none() {
+        if let Err(error) = vm.set_rpl_path(options.rom.with_extension("rpl")) {
+            eprintln!("yaci: failed to read rpl flags: {}", error);
+            return EXIT_USAGE;
+        }
+    }
+
+    let mut recording = options.record.as_ref().map(|_| {
+        Movie::new(
+            platform,
+            quirks,
+            vm.seed().unwrap_or_default(),
+            vm.rom_hash(),
+            cycles_per_frame,
+        )
+    });
+    let mut pacer = if options.headless {
+        None
+    } else {
+        Some(Pacer::new(FRAME_HZ))
+    };
+    let mut frame = 0;
+    let mut status = 0;
+    let mut paused = false;
+    while !vm.should_quit() && options.frames.map_or(true, |frames| frame < frames) {
+        let mut rewind = 0;
+        let mut step = false;
+        while let Some(request) = vm.poll_request() {
+            match request {
+                Request::Rewind(frames) => rewind += frames,
+                Request::SaveState(slot) => save_state(&vm, &options.rom, slot),
+                Request::LoadState(_) if recording.is_some() => {
+                    eprintln!("yaci: states cannot be loaded while recording a movie")
+                }
+                Request::LoadState(slot) => load_state(&mut vm, &options.rom, slot),
+                Request::TogglePause | Request::Step
+                    if recording.is_some() || options.replay.is_some() =>
+                {
+                    eprintln!("yaci: cannot pause while recording or replaying a movie")
+                }
+                Request::TogglePause => paused = !paused,
+                Request::Step => {
+                    paused = true;
+                    step = true;
+                }
+            }
+        }
+        let result = if paused && rewind == 0 {
+            let result = if step { vm.step() } else { Ok(()) };
+            vm.idle();
+            result
+        } else if rewind > 0 {
+            vm.rewind(rewind).map(|rewound| {
+                frame = frame.saturating_sub(rewound as u64);
+                if let Some(movie) = &mut recording {
+                    movie.truncate(frame as usize);
+                }
+            })
+        } else {
+            let result = vm.run_frame(cycles_per_frame);
+            if let Some(movie) = &mut recording {
+                movie.push(vm.keys());
+            }
+            result.map(|()| frame += 1)
+        };
+        if let Err(error) = result {
+            eprintln!(
+                "yaci: crash at frame {} (seed {}): {}",
+                frame,
+                vm.seed().unwrap_or_default(),
+                error
+            );
+            status = EXIT_FAULT;
+            break;
+        }
+        if let Some(pacer) = &mut pacer {
+            pacer.wait();
+        }
+    }
+
+    if let Some(mut tracer) = vm.take_tracer() {
+        if let Err(error) = tracer.flush() {
+            eprintln!("yaci: failed to write trace: {}", error);
+            status = EXIT_IO;
+        }
+    }
+    if let (Some(movie), Some(path)) = (&recording, &options.record) {
+        match movie.save(path) {
+            Ok(()) => eprintln!("Recorded {} frames to {}", movie.len(), path.display()),
+            Err(error) => {
+                eprintln!("yaci: failed to save {}: {}", path.display(), error);
+                status = EXIT_IO;
+            }
+        }
+    }
+    if options.dump_screen {
+        print!("{}", vm.display());
+    }
+    if let Some(path) = &options.screenshot {
+        if let Err(error) = Screenshot::new(1).save(vm.display(), path) {
+            eprintln!("yaci: failed to save {}: {}", path.display(), error);
+            status = EXIT_IO;
+        }
+    }
+
+    status
+}
+
+fn debug(options: Options) -> i32 {
+    let rom = match fs::read(&options.rom) {
+        Ok(rom) => rom,
+        Err(error) => {
+            eprintln!("yaci: failed to read {}: {}", options.rom.display(), error);
+            return EXIT_USAGE;
+        }
+    };
+    let platform = options.platform.unwrap_or_else(|| Platform::detect(&rom));
+    let quirks = options.quirks.unwrap_or_else(|| platform.default_quirks());
+    let frontend = open_frontend(&options);
+    let mut vm = match options.seed {
+        Some(seed) => Chip8Vm::with_seed(frontend, platform, quirks, seed),
+        None => Chip8Vm::with_platform(frontend, platform, quirks),
+    };
+    vm.set_stack_depth(options.stack_depth);
+
+    if let Err(error) = vm.load_rom_bytes(&rom) {
+        eprintln!("yaci: failed to load {}: {}", options.rom.display(), error);
+        return EXIT_USAGE;
     }
+    if platform.rpl_flags() > 0 {
+        if let Err(error) = vm.set_rpl_path(options.rom.with_extension("rpl")) {
+            eprintln!("yaci: failed to read rpl flags: {}", error);
+            return EXIT_USAGE;
+        }
+    }
+
+    if !attach_tracer(&mut vm, &options) {
+        return EXIT_IO;
+    }
+
+    let mut debugger = Debugger::new(vm, (options.cpu_hz / FRAME_HZ).max(1));
+    if !options.headless {
+        debugger.set_pacer(Some(Pacer::new(FRAME_HZ)));
+    }
+    match options.gdb_port {
+        Some(port) => serve_gdb(&mut debugger, port),
+        None => repl::run(&mut debugger),
+    }
+}
 
-    while !vm.should_quit() {
-        if vm.execute_cycle().is_err() {
-            return Err("Crash at execution cycle.");
+fn serve_gdb(debugger: &mut Debugger, port: u16) -> i32 {
+    let listener = match TcpListener::bind(("127.0.0.1", port)) {
+        Ok(listener) => listener,
+        Err(error) => {
+            eprintln!("yaci: failed to listen on port {}: {}", port, error);
+            return EXIT_USAGE;
+        }
+    };
+    eprintln!("Waiting for gdb on 127.0.0.1:{}", port);
+    match gdb::serve(debugger, &listener) {
+        Ok(()) => 0,
+        Err(error) => {
+            eprintln!("yaci: gdb connection failed: {}", error);
+            EXIT_IO
         }
     }
+}
+
+fn attach_tracer(vm: &mut Chip8Vm, options: &Options) -> bool {
+    if options.trace.is_none() && options.trace_range.is_none() && options.trace_ring == 0 {
+        return true;
+    }
+    let output: Box<dyn Write> = match &options.trace {
+        Some(path) if path == Path::new("-") => Box::new(io::stdout()),
+        Some(path) => match File::create(path) {
+            Ok(file) => Box::new(BufWriter::new(file)),
+            Err(error) => {
+                eprintln!("yaci: failed to create {}: {}", path.display(), error);
+                return false;
+            }
+        },
+        None => Box::new(io::stderr()),
+    };
+    let mut tracer = Tracer::new(output);
+    if let Some(range) = &options.trace_range {
+        tracer.set_range(range.clone());
+    }
+    tracer.set_ring_size(options.trace_ring);
+    vm.set_tracer(tracer);
+    true
+}
+
+fn open_frontend(options: &Options) -> Box<dyn FrontEnd> {
+    if options.headless {
+        Box::new(HeadlessFrontEnd::default())
+    } else {
+        let mut frontend = Sdl2FrontEnd::with_scale(options.scale);
+        frontend.set_tone(options.tone_hz, options.volume);
+        Box::new(frontend)
+    }
+}
+
+fn disasm(path: &Path, platform: Option<Platform>) -> i32 {
+    let rom = match fs::read(path) {
+        Ok(rom) => rom,
+        Err(error) => {
+            eprintln!("yaci: failed to read {}: {}", path.display(), error);
+            return EXIT_USAGE;
+        }
+    };
+    let platform = platform.unwrap_or_else(|| Platform::detect(&rom));
+    let mut stdout = io::stdout().lock();
+    for line in disassemble(&rom, platform) {
+        if writeln!(stdout, "{}", line).is_err() {
+            break;
+        }
+    }
+
+    0
+}
+
+fn asm(source: &Path, output: &Path, listing: Option<&Path>) -> i32 {
+    let program = match assemble_file(source) {
+        Ok(program) => program,
+        Err(error) => {
+            eprintln!("yaci: {}", error);
+            return EXIT_USAGE;
+        }
+    };
+    if let Err(error) = fs::write(output, &program.bytes) {
+        eprintln!("yaci: failed to write {}: {}", output.display(), error);
+        return EXIT_IO;
+    }
+    if let Some(path) = listing {
+        if let Err(error) = fs::write(path, &program.listing) {
+            eprintln!("yaci: failed to write {}: {}", path.display(), error);
+            return EXIT_IO;
+        }
+    }
+
+    0
+}
+
+fn save_state(vm: &Chip8Vm, rom: &Path, slot: usize) {
+    let path = state_path(rom, slot);
+    match fs::write(&path, vm.save_state()) {
+        Ok(()) => eprintln!("Saved state to {}", path.display()),
+        Err(error) => eprintln!("yaci: failed to save {}: {}", path.display(), error),
+    }
+}
+
+fn load_state(vm: &mut Chip8Vm, rom: &Path, slot: usize) {
+    let path = state_path(rom, slot);
+    match fs::read(&path)
+        .map_err(Into::into)
+        .and_then(|state| vm.load_state(&state))
+    {
+        Ok(()) => eprintln!("Loaded state from {}", path.display()),
+        Err(error) => eprintln!("yaci: failed to load {}: {}", path.display(), error),
+    }
+}
 
-    Ok(())
+fn state_path(rom: &Path, slot: usize) -> PathBuf {
+    rom.with_extension(format!("state{}", slot))
 }
@@ -0,0 +1,194 @@
+use crate::chip8::memory::DisplayBuffer;
+use crate::chip8::{Platform, Quirks, AUDIO_PATTERN_SIZE};
+use crate::frontend::{DebugInfo, FrontEnd, Keys, Request};
+
+use std::convert::TryInto;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, ErrorKind, Write};
+use std::path::Path;
+
+const MAGIC: &[u8; 4] = b"YMOV";
+const VERSION: u16 = 1;
+const HEADER_SIZE: usize = 32;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Movie {
+    pub platform: Platform,
+    pub quirks: Quirks,
+    pub seed: u64,
+    pub rom_hash: u64,
+    pub cycles_per_frame: u32,
+    frames: Vec<u16>,
+}
+
+impl Movie {
+    pub fn new(
+        platform: Platform,
+        quirks: Quirks,
+        seed: u64,
+        rom_hash: u64,
+        cycles_per_frame: u32,
+    ) -> Self {
+        Self {
+            platform,
+            quirks,
+            seed,
+            rom_hash,
+            cycles_per_frame,
+            frames: Vec::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    pub fn push(&mut self, keys: &Keys) {
+        self.frames.push(keys.to_bits());
+    }
+
+    pub fn truncate(&mut self, frames: usize) {
+        self.frames.truncate(frames);
+    }
+
+    pub fn keys(&self, frame: usize) -> Option<Keys> {
+        self.frames.get(frame).map(|bits| Keys::from_bits(*bits))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write(&mut writer)?;
+        writer.flush()
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::from_bytes(&fs::read(path)?)
+    }
+
+    pub fn write(&self, writer: &mut impl Write) -> io::Result<()> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        writer.write_all(&[self.platform.id(), self.quirks.to_bits()])?;
+        writer.write_all(&self.seed.to_le_bytes())?;
+        writer.write_all(&self.rom_hash.to_le_bytes())?;
+        writer.write_all(&self.cycles_per_frame.to_le_bytes())?;
+        writer.write_all(&(self.frames.len() as u32).to_le_bytes())?;
+        for keys in &self.frames {
+            writer.write_all(&keys.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    pub fn from_bytes(data: &[u8]) -> io::Result<Self> {
+        if data.len() < HEADER_SIZE || &data[..4] != MAGIC {
+            return Err(invalid_data("not a yaci movie"));
+        }
+        let version = u16::from_le_bytes(data[4..6].try_into().unwrap());
+        if version != VERSION {
+            return Err(invalid_data("unsupported movie version"));
+        }
+        let platform =
+            Platform::from_id(data[6]).ok_or_else(|| invalid_data("unknown platform"))?;
+        let quirks = Quirks::from_bits(data[7]);
+        let seed = u64::from_le_bytes(data[8..16].try_into().unwrap());
+        let rom_hash = u64::from_le_bytes(data[16..24].try_into().unwrap());
+        let cycles_per_frame = u32::from_le_bytes(data[24..28].try_into().unwrap());
+        let length = u32::from_le_bytes(data[28..32].try_into().unwrap()) as usize;
+
+        let frames = &data[HEADER_SIZE..];
+        if frames.len() != length * 2 {
+            return Err(invalid_data("movie length does not match its header"));
+        }
+        let mut movie = Self::new(platform, quirks, seed, rom_hash, cycles_per_frame);
+        movie.frames = frames
+            .chunks(2)
+            .map(|keys| u16::from_le_bytes([keys[0], keys[1]]))
+            .collect();
+
+        Ok(movie)
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, message)
+}
+
+pub struct ReplayFrontEnd {
+    inner: Box<dyn FrontEnd>,
+    movie: Movie,
+    frame: usize,
+    keys: Keys,
+}
+
+impl ReplayFrontEnd {
+    pub fn new(inner: Box<dyn FrontEnd>, movie: Movie) -> Self {
+        Self {
+            inner,
+            movie,
+            frame: 0,
+            keys: Keys::default(),
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.frame >= self.movie.len()
+    }
+}
+
+impl FrontEnd for ReplayFrontEnd {
+    fn draw(&mut self, data: &DisplayBuffer) {
+        self.inner.draw(data);
+    }
+
+    fn get_keys(&mut self) -> &Keys {
+        self.inner.get_keys();
+        self.keys = self.movie.keys(self.frame).unwrap_or_default();
+        &self.keys
+    }
+
+    fn set_buzzer(&mut self, on: bool) {
+        self.inner.set_buzzer(on);
+    }
+
+    fn set_audio_pattern(&mut self, pattern: &[u8; AUDIO_PATTERN_SIZE], pitch: u8) {
+        self.inner.set_audio_pattern(pattern, pitch);
+    }
+
+    fn should_quit(&self) -> bool {
+        self.is_finished() || self.inner.should_quit()
+    }
+
+    fn update(&mut self) {
+        self.inner.update();
+    }
+
+    fn end_frame(&mut self) {
+        self.frame += 1;
+        self.inner.end_frame();
+    }
+
+    fn rewind(&mut self, frames: usize) {
+        self.frame = self.frame.saturating_sub(frames);
+        self.inner.rewind(frames);
+    }
+
+    fn poll_request(&mut self) -> Option<Request> {
+        while self.inner.poll_request().is_some() {}
+        None
+    }
+
+    fn wants_debug_info(&self) -> bool {
+        self.inner.wants_debug_info()
+    }
+
+    fn draw_debug_info(&mut self, info: &DebugInfo) {
+        self.inner.draw_debug_info(info);
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,63 @@
+use super::{Movie, ReplayFrontEnd};
+use crate::chip8::{Chip8Vm, Platform, Quirks};
+use crate::frontend::{HeadlessFrontEnd, Key, Keys};
+
+use std::fs;
+
+#[test]
+fn test_movie_bytes() {
+    let mut movie = Movie::new(Platform::SuperChip, Quirks::schip(), 42, 0xABCD, 11);
+    let mut keys = Keys::default();
+    movie.push(&keys);
+    keys[0xA] = Key(true);
+    movie.push(&keys);
+    let mut data = Vec::new();
+
+    movie.write(&mut data).unwrap();
+
+    let loaded = Movie::from_bytes(&data).unwrap();
+    assert_eq!(loaded, movie);
+    assert!(loaded.keys(1).unwrap()[0xA].0);
+    assert!(loaded.keys(2).is_none());
+    assert!(Movie::from_bytes(&data[..data.len() - 1]).is_err());
+    assert!(Movie::from_bytes(b"YMOV").is_err());
+}
+
+#[test]
+fn test_replay() {
+    let rom = fs::read("roms/games/Brix [Andreas Gustafsson, 1990].ch8").unwrap();
+    let headless = HeadlessFrontEnd::default();
+    for frame in (10..300).step_by(20) {
+        headless
+            .press_key(frame, if frame % 40 == 10 { 0x4 } else { 0x6 })
+            .unwrap();
+    }
+    let mut vm = Chip8Vm::with_platform(
+        Box::new(headless.clone()),
+        Platform::Chip8,
+        Quirks::cosmac_vip(),
+    );
+    vm.load_rom_bytes(&rom).unwrap();
+    let mut movie = Movie::new(
+        Platform::Chip8,
+        Quirks::cosmac_vip(),
+        vm.seed().unwrap(),
+        vm.rom_hash(),
+        11,
+    );
+    for _ in 0..300 {
+        vm.run_frame(11).unwrap();
+        movie.push(vm.keys());
+    }
+
+    let replayed = HeadlessFrontEnd::default();
+    let replay = ReplayFrontEnd::new(Box::new(replayed.clone()), movie.clone());
+    let mut vm = Chip8Vm::with_seed(Box::new(replay), movie.platform, movie.quirks, movie.seed);
+    vm.load_rom_bytes(&rom).unwrap();
+    while !vm.should_quit() {
+        vm.run_frame(movie.cycles_per_frame).unwrap();
+    }
+
+    assert_eq!(replayed.frames(), 300);
+    assert_eq!(replayed.screen_bytes(), headless.screen_bytes());
+}
@@ -0,0 +1,248 @@
+use crate::chip8::Fetched;
+use crate::frontend::DebugInfo;
+
+const GLYPH_WIDTH: usize = 3;
+const GLYPH_HEIGHT: usize = 5;
+const CELL_WIDTH: usize = GLYPH_WIDTH + 1;
+const CELL_HEIGHT: usize = GLYPH_HEIGHT + 1;
+
+pub const COLUMNS: usize = 32;
+pub const ROWS: usize = 41;
+pub const PANEL_WIDTH: usize = COLUMNS * CELL_WIDTH + 1;
+pub const PANEL_HEIGHT: usize = ROWS * CELL_HEIGHT + 1;
+
+const CODE_LINES: usize = 8;
+const CODE_BEFORE_PC: usize = 4;
+const MEMORY_LINES: usize = 8;
+const MEMORY_BYTES_PER_LINE: usize = 8;
+const KEYPAD: [[usize; 4]; 4] = [
+    [0x1, 0x2, 0x3, 0xC],
+    [0x4, 0x5, 0x6, 0xD],
+    [0x7, 0x8, 0x9, 0xE],
+    [0xA, 0x0, 0xB, 0xF],
+];
+
+const FONT: [(char, [u8; GLYPH_HEIGHT]); 47] = [
+    ('0', [0b111, 0b101, 0b101, 0b101, 0b111]),
+    ('1', [0b010, 0b110, 0b010, 0b010, 0b111]),
+    ('2', [0b111, 0b001, 0b111, 0b100, 0b111]),
+    ('3', [0b111, 0b001, 0b111, 0b001, 0b111]),
+    ('4', [0b101, 0b101, 0b111, 0b001, 0b001]),
+    ('5', [0b111, 0b100, 0b111, 0b001, 0b111]),
+    ('6', [0b111, 0b100, 0b111, 0b101, 0b111]),
+    ('7', [0b111, 0b001, 0b001, 0b001, 0b001]),
+    ('8', [0b111, 0b101, 0b111, 0b101, 0b111]),
+    ('9', [0b111, 0b101, 0b111, 0b001, 0b111]),
+    ('A', [0b010, 0b101, 0b111, 0b101, 0b101]),
+    ('B', [0b110, 0b101, 0b110, 0b101, 0b110]),
+    ('C', [0b011, 0b100, 0b100, 0b100, 0b011]),
+    ('D', [0b110, 0b101, 0b101, 0b101, 0b110]),
+    ('E', [0b111, 0b100, 0b110, 0b100, 0b111]),
+    ('F', [0b111, 0b100, 0b110, 0b100, 0b100]),
+    ('G', [0b011, 0b100, 0b101, 0b101, 0b011]),
+    ('H', [0b101, 0b101, 0b111, 0b101, 0b101]),
+    ('I', [0b111, 0b010, 0b010, 0b010, 0b111]),
+    ('J', [0b001, 0b001, 0b001, 0b101, 0b010]),
+    ('K', [0b101, 0b101, 0b110, 0b101, 0b101]),
+    ('L', [0b100, 0b100, 0b100, 0b100, 0b111]),
+    ('M', [0b101, 0b111, 0b111, 0b101, 0b101]),
+    ('N', [0b110, 0b101, 0b101, 0b101, 0b101]),
+    ('O', [0b010, 0b101, 0b101, 0b101, 0b010]),
+    ('P', [0b110, 0b101, 0b110, 0b100, 0b100]),
+    ('Q', [0b010, 0b101, 0b101, 0b110, 0b011]),
+    ('R', [0b110, 0b101, 0b110, 0b101, 0b101]),
+    ('S', [0b011, 0b100, 0b010, 0b001, 0b110]),
+    ('T', [0b111, 0b010, 0b010, 0b010, 0b010]),
+    ('U', [0b101, 0b101, 0b101, 0b101, 0b111]),
+    ('V', [0b101, 0b101, 0b101, 0b101, 0b010]),
+    ('W', [0b101, 0b101, 0b111, 0b111, 0b101]),
+    ('X', [0b101, 0b101, 0b010, 0b101, 0b101]),
+    ('Y', [0b101, 0b101, 0b010, 0b010, 0b010]),
+    ('Z', [0b111, 0b001, 0b010, 0b100, 0b111]),
+    (' ', [0b000, 0b000, 0b000, 0b000, 0b000]),
+    (',', [0b000, 0b000, 0b000, 0b010, 0b100]),
+    ('.', [0b000, 0b000, 0b000, 0b000, 0b010]),
+    (':', [0b000, 0b010, 0b000, 0b010, 0b000]),
+    ('-', [0b000, 0b000, 0b111, 0b000, 0b000]),
+    ('=', [0b000, 0b111, 0b000, 0b111, 0b000]),
+    ('>', [0b100, 0b010, 0b001, 0b010, 0b100]),
+    ('[', [0b110, 0b100, 0b100, 0b100, 0b110]),
+    (']', [0b011, 0b001, 0b001, 0b001, 0b011]),
+    ('#', [0b101, 0b111, 0b101, 0b111, 0b101]),
+    ('?', [0b111, 0b001, 0b010, 0b000, 0b010]),
+];
+
+type Cell = (char, bool);
+
+pub struct Panel {
+    cells: Vec<Cell>,
+    pixels: Vec<bool>,
+}
+
+impl Panel {
+    pub fn new() -> Self {
+        Self {
+            cells: vec![(' ', false); COLUMNS * ROWS],
+            pixels: vec![false; PANEL_WIDTH * PANEL_HEIGHT],
+        }
+    }
+
+    pub fn update(&mut self, info: &DebugInfo) -> bool {
+        let cells = layout(info);
+        if cells == self.cells {
+            return false;
+        }
+        for (index, cell) in cells.iter().enumerate() {
+            if *cell != self.cells[index] {
+                self.glyph(index % COLUMNS, index / COLUMNS, *cell);
+            }
+        }
+        self.cells = cells;
+        true
+    }
+
+    pub fn width(&self) -> usize {
+        PANEL_WIDTH
+    }
+
+    pub fn height(&self) -> usize {
+        PANEL_HEIGHT
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+        self.pixels[y * PANEL_WIDTH + x]
+    }
+
+    fn glyph(&mut self, column: usize, row: usize, (c, inverted): Cell) {
+        let rows = glyph(c).unwrap_or_else(|| glyph('?').unwrap());
+        let (left, top) = (column * CELL_WIDTH, row * CELL_HEIGHT);
+        for y in 0..CELL_HEIGHT {
+            for x in 0..CELL_WIDTH {
+                let lit = x >= 1 && y >= 1 && rows[y - 1] & 1 << (GLYPH_WIDTH - x) != 0;
+                self.pixels[(top + y) * PANEL_WIDTH + left + x] = lit != inverted;
+            }
+        }
+    }
+}
+
+impl Default for Panel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn layout(info: &DebugInfo) -> Vec<Cell> {
+    let mut cells = vec![(' ', false); COLUMNS * ROWS];
+    let mut text = |column: usize, row: usize, text: &str, inverted: bool| {
+        for (offset, c) in text.chars().enumerate() {
+            if column + offset < COLUMNS && row < ROWS {
+                cells[row * COLUMNS + column + offset] = (c, inverted);
+            }
+        }
+    };
+
+    let mut status = format!("PC {:03X}  I {:04X}", info.pc, info.i);
+    if info.paused {
+        status += "  PAUSED";
+    }
+    text(0, 0, &status, false);
+    text(
+        0,
+        1,
+        &format!(
+            "SP {}  DT {:02X}  ST {:02X}",
+            info.stack.len(),
+            info.dt,
+            info.st
+        ),
+        false,
+    );
+    for (row, registers) in info.v.chunks(4).enumerate() {
+        let line: Vec<String> = registers
+            .iter()
+            .enumerate()
+            .map(|(x, value)| format!("V{:X} {:02X}", row * 4 + x, value))
+            .collect();
+        text(0, 3 + row, &line.join(" "), false);
+    }
+
+    text(0, 8, "STACK", false);
+    for (row, addresses) in info.stack.chunks(4).enumerate() {
+        let line: Vec<String> = addresses
+            .iter()
+            .map(|address| format!("{:03X}", address))
+            .collect();
+        text(0, 9 + row, &line.join(" "), false);
+    }
+
+    text(0, 14, "CODE", false);
+    let mut address = (info.pc as usize).saturating_sub(CODE_BEFORE_PC);
+    for row in 0..CODE_LINES {
+        let (line, size) = code_line(info, address);
+        text(0, 15 + row, &line, false);
+        address += size;
+    }
+
+    let start = info.i as usize & !(MEMORY_BYTES_PER_LINE - 1);
+    text(0, 24, "MEMORY AT I", false);
+    for row in 0..MEMORY_LINES {
+        let address = start + row * MEMORY_BYTES_PER_LINE;
+        if address >= info.memory.len() {
+            break;
+        }
+        let end = (address + MEMORY_BYTES_PER_LINE).min(info.memory.len());
+        let bytes: Vec<String> = info.memory[address..end]
+            .iter()
+            .map(|byte| format!("{:02X}", byte))
+            .collect();
+        text(
+            0,
+            25 + row,
+            &format!("{:04X} {}", address, bytes.join(" ")),
+            false,
+        );
+    }
+
+    text(0, 34, "KEYS", false);
+    for (row, keys) in KEYPAD.iter().enumerate() {
+        for (column, key) in keys.iter().enumerate() {
+            text(
+                column * 2,
+                35 + row,
+                &format!("{:X}", key),
+                info.keys[*key].0,
+            );
+        }
+    }
+
+    text(0, 40, "F9 PANEL  F10 PAUSE  F11 STEP", false);
+    cells
+}
+
+fn glyph(c: char) -> Option<[u8; GLYPH_HEIGHT]> {
+    let c = c.to_ascii_uppercase();
+    FONT.iter()
+        .find(|(glyph, _)| *glyph == c)
+        .map(|(_, rows)| *rows)
+}
+
+fn code_line(info: &DebugInfo, address: usize) -> (String, usize) {
+    let marker = if address == info.pc as usize {
+        '>'
+    } else {
+        ' '
+    };
+    match Fetched::read_at(info.memory, address, info.platform) {
+        Some(fetched) => (
+            format!(
+                "{}{:03X} {:04X} {}",
+                marker, address, fetched.opcode, fetched
+            ),
+            fetched.size(),
+        ),
+        None => (String::new(), 2),
+    }
+}
+
+#[cfg(test)]
+mod tests;
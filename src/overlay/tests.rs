@@ -0,0 +1,86 @@
+use super::{code_line, glyph, Panel, CELL_HEIGHT, CELL_WIDTH, PANEL_HEIGHT, PANEL_WIDTH};
+use crate::chip8::{Instruction, Platform};
+use crate::frontend::{DebugInfo, Key, Keys};
+
+fn memory() -> Vec<u8> {
+    let mut memory = vec![0; 0x1000];
+    memory[0x200..0x208].copy_from_slice(&[0x60, 0x05, 0xF0, 0x00, 0x12, 0x34, 0x00, 0xE0]);
+    memory
+}
+
+fn debug_info<'a>(memory: &'a [u8], keys: &'a Keys) -> DebugInfo<'a> {
+    DebugInfo {
+        platform: Platform::XoChip,
+        v: [0; 16],
+        i: 0x300,
+        pc: 0x202,
+        stack: &[0x200],
+        dt: 0,
+        st: 0,
+        keys,
+        memory,
+        paused: true,
+    }
+}
+
+fn cell_lit(panel: &Panel, column: usize, row: usize) -> bool {
+    let (left, top) = (column * CELL_WIDTH, row * CELL_HEIGHT);
+    (1..CELL_HEIGHT).any(|y| (1..CELL_WIDTH).any(|x| panel.pixel(left + x, top + y)))
+}
+
+#[test]
+fn test_panel() {
+    let memory = memory();
+    let mut keys = Keys::default();
+    let mut panel = Panel::new();
+
+    assert!(panel.update(&debug_info(&memory, &keys)));
+    assert!(!panel.update(&debug_info(&memory, &keys)));
+    assert_eq!((panel.width(), panel.height()), (PANEL_WIDTH, PANEL_HEIGHT));
+    assert!(cell_lit(&panel, 0, 0));
+    assert!(!cell_lit(&panel, 2, 0));
+    assert!(panel.pixel(1, 1) && panel.pixel(1, 5) && !panel.pixel(2, 2));
+
+    // The 'A' key is the first cell of the last keypad row, drawn inverted.
+    let (left, top) = (0, 38 * CELL_HEIGHT);
+    assert!(!panel.pixel(left, top));
+    keys[0xA] = Key(true);
+    assert!(panel.update(&debug_info(&memory, &keys)));
+    assert!(panel.pixel(left, top) && !panel.pixel(left + 2, top + 1));
+    keys[0xA] = Key(false);
+    assert!(panel.update(&debug_info(&memory, &keys)));
+    assert!(!panel.pixel(left, top) && panel.pixel(left + 2, top + 1));
+}
+
+#[test]
+fn test_code_line() {
+    let memory = memory();
+    let keys = Keys::default();
+    let info = debug_info(&memory, &keys);
+
+    assert_eq!(
+        code_line(&info, 0x200),
+        (" 200 6005 LD V0, 0x05".to_owned(), 2)
+    );
+    assert_eq!(
+        code_line(&info, 0x202),
+        (">202 F000 LD I, LONG 0x1234".to_owned(), 4)
+    );
+    assert_eq!(code_line(&info, 0x1000), (String::new(), 2));
+}
+
+#[test]
+fn test_font_covers_mnemonics() {
+    for platform in &[Platform::Chip8, Platform::SuperChip, Platform::XoChip] {
+        for opcode in 0..=0xFFFF {
+            if let Ok(instruction) = Instruction::decode(opcode, *platform) {
+                let text = instruction.to_string();
+                assert!(
+                    text.chars().all(|c| glyph(c).is_some()),
+                    "no glyph for {:?}",
+                    text
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,27 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub struct Pacer {
+    frame_duration: Duration,
+    next_frame: Instant,
+}
+
+impl Pacer {
+    pub fn new(frames_per_second: u32) -> Self {
+        let frame_duration = Duration::from_secs(1) / frames_per_second.max(1);
+        Self {
+            frame_duration,
+            next_frame: Instant::now() + frame_duration,
+        }
+    }
+
+    pub fn wait(&mut self) {
+        let now = Instant::now();
+        if let Some(remaining) = self.next_frame.checked_duration_since(now) {
+            thread::sleep(remaining);
+            self.next_frame += self.frame_duration;
+        } else {
+            self.next_frame = now + self.frame_duration;
+        }
+    }
+}
@@ -0,0 +1,341 @@
+use yaci::chip8::{Access, Fetched, VmError};
+use yaci::debugger::{Condition, Debugger, Register, Stop, WatchKind, Watchpoint};
+
+use std::convert::TryFrom;
+use std::io::{self, BufRead, Write};
+
+const HELP: &str = "\
+commands (addresses and values are hex, counts are decimal):
+    break [addr]           set a breakpoint, or list them without an address
+    delete <addr>          remove a breakpoint
+    watch [addr] [end] [r|w|rw]
+                           stop when memory is read or written (default w),
+                           or list watchpoints without an address
+    unwatch <n>            remove watchpoint n
+    cond [<reg> <op> <value>]
+                           stop when a comparison such as V3 == 10 becomes
+                           true, or list conditions without arguments
+    uncond <n>             remove condition n
+    step [n]               execute n instructions (default 1)
+    next                   step over a CALL
+    finish                 run until the current subroutine returns
+    continue               run until a breakpoint or the rom exits
+    regs                   print V0-VF, I, PC, SP and the timers
+    stack                  print the call stack
+    x <addr> [n]           dump n bytes of memory (default 64)
+    list [addr] [n]        disassemble n instructions (default 8 at PC)
+    set <reg> <value>      set V0-VF, I, PC, DT or ST
+    set <addr> <bytes..>   write bytes to memory
+    help                   print this message
+    quit                   leave the debugger
+
+an empty line repeats the previous command";
+
+const DUMP_BYTES: usize = 64;
+const LIST_LINES: usize = 8;
+
+pub fn run(debugger: &mut Debugger) -> i32 {
+    print_location(debugger);
+
+    let stdin = io::stdin();
+    let mut previous = String::new();
+    loop {
+        print!("(yaci) ");
+        io::stdout().flush().ok();
+
+        let mut line = String::new();
+        match stdin.lock().read_line(&mut line) {
+            Ok(0) | Err(_) => return 0,
+            Ok(_) => {}
+        }
+        if line.trim().is_empty() {
+            line = previous.clone();
+        } else {
+            previous = line.clone();
+        }
+
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match execute(debugger, &words) {
+            Ok(true) => {}
+            Ok(false) => return 0,
+            Err(error) => println!("{}", error),
+        }
+    }
+}
+
+fn execute(debugger: &mut Debugger, words: &[&str]) -> Result<bool, String> {
+    let (command, args) = match words.split_first() {
+        Some((command, args)) => (*command, args),
+        None => return Ok(true),
+    };
+    match command {
+        "b" | "break" => match args {
+            [] => {
+                for address in debugger.breakpoints() {
+                    println!("breakpoint at {:03X}", address);
+                }
+            }
+            [address] => {
+                let address = parse_hex(address)?;
+                if debugger.add_breakpoint(address) {
+                    println!("breakpoint set at {:03X}", address);
+                }
+            }
+            _ => return Err("usage: break [addr]".to_owned()),
+        },
+        "d" | "delete" => match args {
+            [address] => {
+                let address = parse_hex(address)?;
+                if !debugger.remove_breakpoint(address) {
+                    return Err(format!("no breakpoint at {:03X}", address));
+                }
+            }
+            _ => return Err("usage: delete <addr>".to_owned()),
+        },
+        "w" | "watch" => {
+            if args.is_empty() {
+                for (index, watchpoint) in debugger.watchpoints().iter().enumerate() {
+                    println!("{}: {}", index, watchpoint);
+                }
+                return Ok(true);
+            }
+            let (kind, addresses) = match args.split_last() {
+                Some((&"r", addresses)) => (WatchKind::Read, addresses),
+                Some((&"w", addresses)) => (WatchKind::Write, addresses),
+                Some((&"rw", addresses)) => (WatchKind::Access, addresses),
+                _ => (WatchKind::Write, args),
+            };
+            let (start, end) = match addresses {
+                [address] => (parse_hex(address)?, parse_hex(address)?),
+                [start, end] => (parse_hex(start)?, parse_hex(end)?),
+                _ => return Err("usage: watch [addr] [end] [r|w|rw]".to_owned()),
+            };
+            if start > end {
+                return Err("watch range ends before it starts".to_owned());
+            }
+            let watchpoint = Watchpoint::new(start..=end, kind);
+            println!(
+                "watchpoint {}: {}",
+                debugger.watchpoints().len(),
+                watchpoint
+            );
+            debugger.add_watchpoint(watchpoint);
+        }
+        "unwatch" => match args {
+            [index] => {
+                let index = index.parse().map_err(|_| "usage: unwatch <n>".to_owned())?;
+                if debugger.remove_watchpoint(index).is_none() {
+                    return Err(format!("no watchpoint {}", index));
+                }
+            }
+            _ => return Err("usage: unwatch <n>".to_owned()),
+        },
+        "cond" => {
+            if args.is_empty() {
+                for (index, condition) in debugger.conditions().enumerate() {
+                    println!("{}: {}", index, condition);
+                }
+                return Ok(true);
+            }
+            let text = args.join(" ");
+            let condition =
+                Condition::parse(&text).ok_or_else(|| format!("invalid condition '{}'", text))?;
+            println!("condition {}: {}", debugger.conditions().count(), condition);
+            debugger.add_condition(condition);
+        }
+        "uncond" => match args {
+            [index] => {
+                let index = index.parse().map_err(|_| "usage: uncond <n>".to_owned())?;
+                if debugger.remove_condition(index).is_none() {
+                    return Err(format!("no condition {}", index));
+                }
+            }
+            _ => return Err("usage: uncond <n>".to_owned()),
+        },
+        "s" | "step" => {
+            let count = match args {
+                [] => 1,
+                [count] => parse_count(count)?,
+                _ => return Err("usage: step [n]".to_owned()),
+            };
+            return Ok(report(debugger, |debugger| debugger.step(count as u64)));
+        }
+        "n" | "next" => return Ok(report(debugger, Debugger::step_over)),
+        "f" | "finish" => {
+            if debugger.vm().stack().is_empty() {
+                return Err("not inside a subroutine".to_owned());
+            }
+            return Ok(report(debugger, Debugger::step_out));
+        }
+        "c" | "continue" => return Ok(report(debugger, Debugger::resume)),
+        "r" | "regs" => print_registers(debugger),
+        "bt" | "stack" => print_stack(debugger),
+        "x" => {
+            let (address, count) = match args {
+                [address] => (parse_hex(address)?, DUMP_BYTES),
+                [address, count] => (parse_hex(address)?, parse_count(count)?),
+                _ => return Err("usage: x <addr> [n]".to_owned()),
+            };
+            print_memory(debugger, address as usize, count);
+        }
+        "l" | "list" => {
+            let (address, count) = match args {
+                [] => (debugger.vm().pc(), LIST_LINES),
+                [address] => (parse_hex(address)?, LIST_LINES),
+                [address, count] => (parse_hex(address)?, parse_count(count)?),
+                _ => return Err("usage: list [addr] [n]".to_owned()),
+            };
+            print_listing(debugger, address as usize, count);
+        }
+        "set" => match args {
+            [target, value] if Register::from_name(target).is_some() => {
+                let register = Register::from_name(target).unwrap();
+                let value = parse_hex(value)?;
+                if !register.set(debugger.vm_mut(), value) {
+                    return Err(format!("{} cannot be set to {:X}", register, value));
+                }
+            }
+            [address, bytes @ ..] if !bytes.is_empty() => {
+                let address = parse_hex(address)? as usize;
+                let bytes = bytes
+                    .iter()
+                    .map(|byte| parse_byte(byte))
+                    .collect::<Result<Vec<u8>, String>>()?;
+                let memory = debugger.vm_mut().memory_mut();
+                if address + bytes.len() > memory.len() {
+                    return Err("write runs past the end of memory".to_owned());
+                }
+                memory[address..address + bytes.len()].copy_from_slice(&bytes);
+            }
+            _ => return Err("usage: set <reg> <value> or set <addr> <bytes..>".to_owned()),
+        },
+        "h" | "help" => println!("{}", HELP),
+        "q" | "quit" => return Ok(false),
+        _ => return Err(format!("unknown command '{}', try 'help'", command)),
+    }
+
+    Ok(true)
+}
+
+fn report(
+    debugger: &mut Debugger,
+    run: impl FnOnce(&mut Debugger) -> Result<Stop, VmError>,
+) -> bool {
+    match run(debugger) {
+        Ok(Stop::Step) => {}
+        Ok(Stop::Breakpoint(address)) => println!("breakpoint at {:03X}", address),
+        Ok(Stop::Watchpoint {
+            pc,
+            instruction,
+            access,
+        }) => {
+            let (verb, address) = match access {
+                Access::Read(address) => ("read", address),
+                Access::Write(address) => ("write", address),
+            };
+            println!("{} of {:03X} by {:03X}  {}", verb, address, pc, instruction);
+        }
+        Ok(Stop::Condition {
+            pc,
+            instruction,
+            condition,
+        }) => println!("{} after {:03X}  {}", condition, pc, instruction),
+        Ok(Stop::Exited) => println!("rom exited"),
+        Ok(Stop::Quit) => {
+            println!("window closed");
+            return false;
+        }
+        Err(error) => println!("fault at cycle {}: {}", debugger.cycle(), error),
+    }
+    print_location(debugger);
+    true
+}
+
+fn print_location(debugger: &Debugger) {
+    print_listing(debugger, debugger.vm().pc() as usize, 1);
+}
+
+fn print_registers(debugger: &Debugger) {
+    let vm = debugger.vm();
+    let v = vm.v();
+    for (row, values) in v.chunks(8).enumerate() {
+        let registers: Vec<String> = values
+            .iter()
+            .enumerate()
+            .map(|(x, value)| format!("V{:X} {:02X}", row * 8 + x, value))
+            .collect();
+        println!("{}", registers.join("  "));
+    }
+    println!(
+        "I {:04X}  PC {:03X}  SP {}  DT {:02X}  ST {:02X}  cycle {}",
+        vm.i(),
+        vm.pc(),
+        vm.stack().len(),
+        vm.delay_timer(),
+        vm.sound_timer(),
+        debugger.cycle()
+    );
+}
+
+fn print_stack(debugger: &Debugger) {
+    let stack = debugger.vm().stack();
+    if stack.is_empty() {
+        println!("call stack is empty");
+    }
+    for (depth, address) in stack.iter().enumerate().rev() {
+        println!("#{:<2} called from {:03X}", depth, address);
+    }
+}
+
+fn print_memory(debugger: &Debugger, address: usize, count: usize) {
+    let memory = debugger.vm().memory();
+    let end = address.saturating_add(count).min(memory.len());
+    for start in (address..end).step_by(16) {
+        let bytes: Vec<String> = memory[start..(start + 16).min(end)]
+            .iter()
+            .map(|byte| format!("{:02X}", byte))
+            .collect();
+        println!("{:04X}  {}", start, bytes.join(" "));
+    }
+}
+
+fn print_listing(debugger: &Debugger, address: usize, count: usize) {
+    let vm = debugger.vm();
+    let mut address = address;
+    for _ in 0..count {
+        let fetched = match Fetched::read_at(vm.memory(), address, vm.platform()) {
+            Some(fetched) => fetched,
+            None => break,
+        };
+        let marker = if address == vm.pc() as usize {
+            '>'
+        } else {
+            ' '
+        };
+        let operand = fetched
+            .operand
+            .map_or_else(|| "    ".to_owned(), |operand| format!("{:04X}", operand));
+        println!(
+            "{} {:03X}  {:04X} {}  {}",
+            marker, address, fetched.opcode, operand, fetched
+        );
+        address += fetched.size();
+    }
+}
+
+fn parse_hex(value: &str) -> Result<u16, String> {
+    let digits = value.trim_start_matches("0x").trim_start_matches("0X");
+    u16::from_str_radix(digits, 16).map_err(|_| format!("invalid hex value '{}'", value))
+}
+
+fn parse_byte(value: &str) -> Result<u8, String> {
+    let byte = parse_hex(value)?;
+    u8::try_from(byte).map_err(|_| format!("{:X} does not fit in a byte", byte))
+}
+
+fn parse_count(value: &str) -> Result<usize, String> {
+    match value.parse() {
+        Ok(count) if count > 0 => Ok(count),
+        _ => Err(format!("invalid count '{}'", value)),
+    }
+}
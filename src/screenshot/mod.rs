@@ -0,0 +1,189 @@
+use crate::chip8::memory::DisplayBuffer;
+
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::iter;
+use std::path::Path;
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+const MAX_STORED_BLOCK: usize = 0xFFFF;
+const MAX_PLAIN_LINE: usize = 70;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rgb(pub u8, pub u8, pub u8);
+
+impl Rgb {
+    fn luma(self) -> u8 {
+        ((299 * self.0 as u32 + 587 * self.1 as u32 + 114 * self.2 as u32) / 1000) as u8
+    }
+}
+
+pub struct Screenshot {
+    pub scale: usize,
+    pub palette: [Rgb; 4],
+}
+
+impl Screenshot {
+    pub fn new(scale: usize) -> Self {
+        Self {
+            scale: scale.max(1),
+            palette: [
+                Rgb(0, 0, 0),
+                Rgb(0, 255, 0),
+                Rgb(0, 128, 255),
+                Rgb(255, 255, 255),
+            ],
+        }
+    }
+
+    pub fn with_colors(scale: usize, off: Rgb, on: Rgb) -> Self {
+        Self {
+            scale: scale.max(1),
+            palette: [off, on, on, on],
+        }
+    }
+
+    pub fn save(&self, buffer: &DisplayBuffer, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        let mut writer = BufWriter::new(File::create(path)?);
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("pbm") => self.write_pbm(buffer, &mut writer)?,
+            Some("pgm") => self.write_pgm(buffer, &mut writer)?,
+            _ => self.write_png(buffer, &mut writer)?,
+        }
+        writer.flush()
+    }
+
+    pub fn write_pbm(&self, buffer: &DisplayBuffer, writer: &mut impl Write) -> io::Result<()> {
+        let (width, height) = self.size(buffer);
+        writeln!(writer, "P1\n{} {}", width, height)?;
+        for row in self.rows(buffer) {
+            let dark = row
+                .iter()
+                .map(|pixel| (self.color(*pixel).luma() < 128) as u8);
+            write_plain_row(writer, dark)?;
+        }
+        Ok(())
+    }
+
+    pub fn write_pgm(&self, buffer: &DisplayBuffer, writer: &mut impl Write) -> io::Result<()> {
+        let (width, height) = self.size(buffer);
+        writeln!(writer, "P2\n{} {}\n255", width, height)?;
+        for row in self.rows(buffer) {
+            write_plain_row(writer, row.iter().map(|pixel| self.color(*pixel).luma()))?;
+        }
+        Ok(())
+    }
+
+    pub fn write_png(&self, buffer: &DisplayBuffer, writer: &mut impl Write) -> io::Result<()> {
+        let (width, height) = self.size(buffer);
+        let mut header = Vec::with_capacity(13);
+        header.extend_from_slice(&(width as u32).to_be_bytes());
+        header.extend_from_slice(&(height as u32).to_be_bytes());
+        header.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+        let mut image = Vec::with_capacity(height * (width * 3 + 1));
+        for row in self.rows(buffer) {
+            image.push(0);
+            for pixel in row {
+                let Rgb(red, green, blue) = self.color(pixel);
+                image.extend_from_slice(&[red, green, blue]);
+            }
+        }
+
+        writer.write_all(&PNG_SIGNATURE)?;
+        write_chunk(writer, b"IHDR", &header)?;
+        write_chunk(writer, b"IDAT", &zlib_stored(&image))?;
+        write_chunk(writer, b"IEND", &[])
+    }
+
+    fn size(&self, buffer: &DisplayBuffer) -> (usize, usize) {
+        (buffer.width() * self.scale, buffer.height() * self.scale)
+    }
+
+    fn color(&self, pixel: u8) -> Rgb {
+        self.palette[pixel as usize % self.palette.len()]
+    }
+
+    fn rows<'a>(&'a self, buffer: &'a DisplayBuffer) -> impl Iterator<Item = Vec<u8>> + 'a {
+        buffer.data.chunks(buffer.width()).flat_map(move |row| {
+            let scaled: Vec<u8> = row
+                .iter()
+                .flat_map(|pixel| iter::repeat(*pixel).take(self.scale))
+                .collect();
+            iter::repeat(scaled).take(self.scale)
+        })
+    }
+}
+
+fn write_plain_row(
+    writer: &mut impl Write,
+    values: impl Iterator<Item = impl fmt::Display>,
+) -> io::Result<()> {
+    let mut line = String::new();
+    for value in values {
+        let value = value.to_string();
+        if !line.is_empty() && line.len() + 1 + value.len() > MAX_PLAIN_LINE {
+            writeln!(writer, "{}", line)?;
+            line.clear();
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(&value);
+    }
+    writeln!(writer, "{}", line)
+}
+
+fn write_chunk(writer: &mut impl Write, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    writer.write_all(&(data.len() as u32).to_be_bytes())?;
+    writer.write_all(kind)?;
+    writer.write_all(data)?;
+    let mut crc = crc32(0xFFFF_FFFF, kind);
+    crc = crc32(crc, data);
+    writer.write_all(&(crc ^ 0xFFFF_FFFF).to_be_bytes())
+}
+
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x01];
+    let mut blocks = data.chunks(MAX_STORED_BLOCK).peekable();
+    if blocks.peek().is_none() {
+        out.extend_from_slice(&[1, 0, 0, 0xFF, 0xFF]);
+    }
+    while let Some(block) = blocks.next() {
+        let length = block.len() as u16;
+        out.push(blocks.peek().is_none() as u8);
+        out.extend_from_slice(&length.to_le_bytes());
+        out.extend_from_slice(&(!length).to_le_bytes());
+        out.extend_from_slice(block);
+    }
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+fn crc32(mut crc: u32, data: &[u8]) -> u32 {
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                0xEDB8_8320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for byte in data {
+        a = (a + *byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    b << 16 | a
+}
+
+#[cfg(test)]
+mod tests;
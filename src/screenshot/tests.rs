@@ -0,0 +1,62 @@
+use super::{adler32, crc32, Rgb, Screenshot};
+use crate::chip8::memory::DisplayBuffer;
+
+#[test]
+fn test_checksums() {
+    assert_eq!(crc32(0xFFFF_FFFF, b"IEND") ^ 0xFFFF_FFFF, 0xAE42_6082);
+    assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+}
+
+#[test]
+fn test_pbm() {
+    let mut buffer = DisplayBuffer::new(2, 1);
+    buffer.data[1] = 1;
+    let mut out = Vec::new();
+
+    Screenshot::new(2).write_pbm(&buffer, &mut out).unwrap();
+
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "P1\n4 2\n1 1 0 0\n1 1 0 0\n"
+    );
+
+    let mut out = Vec::new();
+    Screenshot::with_colors(1, Rgb(255, 255, 255), Rgb(0, 0, 0))
+        .write_pbm(&DisplayBuffer::default(), &mut out)
+        .unwrap();
+    let out = String::from_utf8(out).unwrap();
+
+    assert!(out.lines().all(|line| line.len() <= 70));
+    assert_eq!(out.lines().nth(2), Some(["0"; 35].join(" ").as_str()));
+    assert_eq!(out.split_whitespace().count(), 3 + 64 * 32);
+}
+
+#[test]
+fn test_pgm() {
+    let mut buffer = DisplayBuffer::new(2, 1);
+    buffer.data[0] = 1;
+    let mut out = Vec::new();
+
+    Screenshot::with_colors(1, Rgb(0, 0, 0), Rgb(255, 255, 255))
+        .write_pgm(&buffer, &mut out)
+        .unwrap();
+
+    assert_eq!(String::from_utf8(out).unwrap(), "P2\n2 1\n255\n255 0\n");
+}
+
+#[test]
+fn test_png() {
+    let buffer = DisplayBuffer::default();
+    let mut out = Vec::new();
+
+    Screenshot::new(3).write_png(&buffer, &mut out).unwrap();
+
+    assert_eq!(
+        out[..8],
+        [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n']
+    );
+    assert_eq!(out[12..16], *b"IHDR");
+    assert_eq!(out[16..20], 192u32.to_be_bytes());
+    assert_eq!(out[20..24], 96u32.to_be_bytes());
+    assert_eq!(out[out.len() - 8..out.len() - 4], *b"IEND");
+}
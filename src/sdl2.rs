@@ -1,23 +1,100 @@
 extern crate sdl2;
 
+use crate::chip8::memory::display::{LORES_HEIGHT, LORES_WIDTH};
 use crate::chip8::memory::DisplayBuffer;
-use crate::frontend::{FrontEnd, Key, Keys};
+use crate::chip8::AUDIO_PATTERN_SIZE;
+use crate::frontend::{DebugInfo, FrontEnd, Key, Keys, Request};
+use crate::overlay::Panel;
+use crate::screenshot::Screenshot;
 
+use sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
 use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
+use sdl2::keyboard::{Keycode, Mod};
 use sdl2::pixels::Color;
 use sdl2::rect::Rect;
 use sdl2::render::WindowCanvas;
 use sdl2::EventPump;
 
+use std::collections::VecDeque;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const PALETTE: [Color; 4] = [
+    Color::RGB(0, 0, 0),
+    Color::RGB(0, 255, 0),
+    Color::RGB(0, 128, 255),
+    Color::RGB(255, 255, 255),
+];
+
+const PANEL_BACKGROUND: Color = Color::RGB(24, 24, 32);
+const PANEL_FOREGROUND: Color = Color::RGB(200, 200, 200);
+
+pub const DEFAULT_TONE_HZ: f32 = 440.0;
+pub const DEFAULT_VOLUME: f32 = 0.25;
+
+const PATTERN_BITS: usize = AUDIO_PATTERN_SIZE * 8;
+
+// XO-CHIP plays the pattern at 4000 bits per second at pitch 64, one octave
+// per 48 steps of pitch.
+fn pattern_rate(pitch: u8) -> f32 {
+    4000.0 * 2f32.powf((pitch as f32 - 64.0) / 48.0)
+}
+
+struct Buzzer {
+    phase: f32,
+    frequency: f32,
+    sample_rate: f32,
+    volume: f32,
+    pattern: Option<([u8; AUDIO_PATTERN_SIZE], f32)>,
+}
+
+impl AudioCallback for Buzzer {
+    type Channel = f32;
+
+    fn callback(&mut self, out: &mut [f32]) {
+        // One phase cycle is a full square wave period or the whole pattern.
+        let step = match &self.pattern {
+            Some((_, rate)) => rate / PATTERN_BITS as f32,
+            None => self.frequency,
+        } / self.sample_rate;
+        for sample in out.iter_mut() {
+            let high = match &self.pattern {
+                Some((pattern, _)) => {
+                    let bit = (self.phase * PATTERN_BITS as f32) as usize;
+                    pattern[bit / 8] & 0x80 >> (bit % 8) != 0
+                }
+                None => self.phase < 0.5,
+            };
+            *sample = if high { self.volume } else { -self.volume };
+            self.phase = (self.phase + step) % 1.0;
+        }
+    }
+}
+
 pub struct Sdl2FrontEnd {
+    audio: Option<AudioDevice<Buzzer>>,
     canvas: WindowCanvas,
     event_pump: EventPump,
+    dirty: bool,
     keys: Keys,
+    overlay: bool,
+    panel: Option<Panel>,
+    requests: VecDeque<Request>,
+    rewinding: bool,
     scale: u32,
+    screen: DisplayBuffer,
     should_quit: bool,
 }
 
+fn get_state_slot(keycode: &Keycode) -> Option<usize> {
+    match keycode {
+        Keycode::F1 => Some(1),
+        Keycode::F2 => Some(2),
+        Keycode::F3 => Some(3),
+        Keycode::F4 => Some(4),
+        _ => None,
+    }
+}
+
 fn get_key_index(keycode: &Keycode) -> usize {
     match keycode {
         Keycode::Num1 => 0x1,
@@ -42,54 +119,70 @@ fn get_key_index(keycode: &Keycode) -> usize {
 
 impl Sdl2FrontEnd {
     pub fn new() -> Self {
+        Self::with_scale(20)
+    }
+
+    pub fn with_scale(scale: u32) -> Self {
         let sdl_context = sdl2::init().unwrap();
         let canvas = sdl_context
             .video()
             .unwrap()
-            .window("chip8", 1280, 640)
+            .window(
+                "chip8",
+                LORES_WIDTH as u32 * scale,
+                LORES_HEIGHT as u32 * scale,
+            )
             .position_centered()
             .build()
             .unwrap()
             .into_canvas()
             .build()
             .unwrap();
+        let audio = sdl_context.audio().ok().and_then(|audio| {
+            let desired = AudioSpecDesired {
+                freq: Some(44100),
+                channels: Some(1),
+                samples: None,
+            };
+            audio
+                .open_playback(None, &desired, |spec| Buzzer {
+                    phase: 0.0,
+                    frequency: DEFAULT_TONE_HZ,
+                    sample_rate: spec.freq as f32,
+                    volume: DEFAULT_VOLUME,
+                    pattern: None,
+                })
+                .ok()
+        });
         Sdl2FrontEnd {
+            audio,
             canvas,
+            dirty: false,
             event_pump: sdl_context.event_pump().unwrap(),
             keys: Keys::default(),
-            scale: 20,
+            overlay: false,
+            panel: None,
+            requests: VecDeque::new(),
+            rewinding: false,
+            scale,
+            screen: DisplayBuffer::default(),
             should_quit: false,
         }
     }
 
-    fn update_should_quit(&mut self, should_quit: bool) {
-        if should_quit {
-            self.should_quit = should_quit;
+    pub fn set_tone(&mut self, frequency: f32, volume: f32) {
+        if let Some(audio) = &mut self.audio {
+            let mut buzzer = audio.lock();
+            buzzer.frequency = frequency;
+            buzzer.volume = volume.clamp(0.0, 1.0);
         }
     }
-}
 
-impl FrontEnd for Sdl2FrontEnd {
-    fn draw(&mut self, data: &DisplayBuffer) {
-        self.canvas.clear();
-
-        for (i, pixel) in data.data.iter().enumerate() {
-            let color: u8 = 255 * pixel;
-            self.canvas.set_draw_color(Color::RGB(0, color, 0));
-            let x = (i % 64) as i32 * self.scale as i32;
-            let y = (i / 64) as i32 * self.scale as i32;
-            self.canvas
-                .fill_rect(Rect::new(x, y, self.scale, self.scale))
-                .expect("Could not draw on canvas");
-        }
-
-        self.canvas.present();
-    }
-
-    fn get_keys(&mut self) -> &Keys {
+    fn poll_events(&mut self) {
         let mut keys_down: Vec<usize> = vec![];
         let mut keys_up: Vec<usize> = vec![];
         let mut should_quit = false;
+        let mut screenshot = false;
 
         for event in self.event_pump.poll_iter() {
             match event {
@@ -98,6 +191,52 @@ impl FrontEnd for Sdl2FrontEnd {
                     keycode: Some(Keycode::Escape),
                     ..
                 } => should_quit = true,
+                Event::KeyDown {
+                    keycode: Some(Keycode::F12),
+                    repeat: false,
+                    ..
+                } => screenshot = true,
+                Event::KeyDown {
+                    keycode: Some(Keycode::F9),
+                    repeat: false,
+                    ..
+                } => {
+                    self.overlay = !self.overlay;
+                    self.panel = None;
+                    self.dirty = true;
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::F10),
+                    repeat: false,
+                    ..
+                } => self.requests.push_back(Request::TogglePause),
+                Event::KeyDown {
+                    keycode: Some(Keycode::F11),
+                    ..
+                } => self.requests.push_back(Request::Step),
+                Event::KeyDown {
+                    keycode: Some(Keycode::Backspace),
+                    ..
+                } => self.rewinding = true,
+                Event::KeyUp {
+                    keycode: Some(Keycode::Backspace),
+                    ..
+                } => self.rewinding = false,
+                Event::KeyDown {
+                    keycode: Some(keycode),
+                    keymod,
+                    repeat: false,
+                    ..
+                } if get_state_slot(&keycode).is_some() => {
+                    let slot = get_state_slot(&keycode).unwrap();
+                    self.requests.push_back(
+                        if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) {
+                            Request::SaveState(slot)
+                        } else {
+                            Request::LoadState(slot)
+                        },
+                    );
+                }
                 Event::KeyDown {
                     keycode: Some(keycode),
                     ..
@@ -114,62 +253,160 @@ impl FrontEnd for Sdl2FrontEnd {
             }
         }
 
-        for key in keys_down {
+        for key in keys_down.into_iter().filter(|key| *key <= 0xF) {
             self.keys[key] = Key(true);
         }
-        for key in keys_up {
+        for key in keys_up.into_iter().filter(|key| *key <= 0xF) {
             self.keys[key] = Key(false);
         }
         self.update_should_quit(should_quit);
+        if screenshot {
+            self.save_screenshot();
+        }
+    }
 
+    fn save_screenshot(&self) {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_millis())
+            .unwrap_or_default();
+        let path = format!("yaci-{}.png", timestamp);
+        let scale = (self.scale as usize * LORES_WIDTH / self.screen.width()).max(1);
+        match Screenshot::new(scale).save(&self.screen, &path) {
+            Ok(()) => eprintln!("Saved screenshot to {}", path),
+            Err(error) => eprintln!("Could not save screenshot: {}", error),
+        }
+    }
+
+    fn render(&mut self) {
+        self.dirty = false;
+        self.canvas.set_draw_color(PALETTE[0]);
+        self.canvas.clear();
+
+        let width = self.screen.width();
+        let size = (self.scale * LORES_WIDTH as u32 / width as u32).max(1);
+        let screen_size = (width as u32 * size, self.screen.height() as u32 * size);
+        let panel = self.panel.as_ref().filter(|_| self.overlay);
+        let panel_scale = panel.map_or(1, |panel| (screen_size.1 / panel.height() as u32).max(1));
+        let window_size = match panel {
+            Some(panel) => (
+                screen_size.0 + panel.width() as u32 * panel_scale,
+                screen_size.1.max(panel.height() as u32 * panel_scale),
+            ),
+            None => screen_size,
+        };
+        if self.canvas.window().size() != window_size {
+            self.canvas
+                .window_mut()
+                .set_size(window_size.0, window_size.1)
+                .expect("Could not resize window");
+        }
+        for (i, pixel) in self.screen.data.iter().enumerate() {
+            self.canvas
+                .set_draw_color(PALETTE[*pixel as usize % PALETTE.len()]);
+            let x = (i % width) as i32 * size as i32;
+            let y = (i / width) as i32 * size as i32;
+            self.canvas
+                .fill_rect(Rect::new(x, y, size, size))
+                .expect("Could not draw on canvas");
+        }
+
+        if let Some(panel) = panel {
+            let left = screen_size.0 as i32;
+            self.canvas.set_draw_color(PANEL_BACKGROUND);
+            self.canvas
+                .fill_rect(Rect::new(
+                    left,
+                    0,
+                    window_size.0 - screen_size.0,
+                    window_size.1,
+                ))
+                .expect("Could not draw on canvas");
+            let mut rects = Vec::new();
+            for y in 0..panel.height() {
+                for x in (0..panel.width()).filter(|x| panel.pixel(*x, y)) {
+                    rects.push(Rect::new(
+                        left + (x as u32 * panel_scale) as i32,
+                        (y as u32 * panel_scale) as i32,
+                        panel_scale,
+                        panel_scale,
+                    ));
+                }
+            }
+            self.canvas.set_draw_color(PANEL_FOREGROUND);
+            self.canvas
+                .fill_rects(&rects)
+                .expect("Could not draw on canvas");
+        }
+
+        self.canvas.present();
+    }
+
+    fn update_should_quit(&mut self, should_quit: bool) {
+        if should_quit {
+            self.should_quit = should_quit;
+        }
+    }
+}
+
+impl Default for Sdl2FrontEnd {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FrontEnd for Sdl2FrontEnd {
+    fn draw(&mut self, data: &DisplayBuffer) {
+        self.screen = data.clone();
+        self.dirty = true;
+    }
+
+    fn get_keys(&mut self) -> &Keys {
+        self.poll_events();
         &self.keys
     }
 
+    fn set_buzzer(&mut self, on: bool) {
+        if let Some(audio) = &self.audio {
+            if on {
+                audio.resume();
+            } else {
+                audio.pause();
+            }
+        }
+    }
+
+    fn set_audio_pattern(&mut self, pattern: &[u8; AUDIO_PATTERN_SIZE], pitch: u8) {
+        if let Some(audio) = &mut self.audio {
+            audio.lock().pattern = Some((*pattern, pattern_rate(pitch)));
+        }
+    }
+
     fn should_quit(&self) -> bool {
         self.should_quit
     }
 
     fn update(&mut self) {
-        let mut should_quit = false;
-        for event in self.event_pump.poll_iter() {
-            match event {
-                Event::Quit { .. }
-                | Event::KeyDown {
-                    keycode: Some(Keycode::Escape),
-                    ..
-                } => should_quit = true,
-                _ => {}
-            }
+        self.poll_events();
+        if self.rewinding {
+            self.requests.push_back(Request::Rewind(1));
+        }
+        if self.dirty {
+            self.render();
         }
-        self.update_should_quit(should_quit);
     }
 
-    fn wait_for_keypress(&mut self) -> u8 {
-        loop {
-            let mut should_quit = false;
-            for event in self.event_pump.poll_iter() {
-                match event {
-                    Event::Quit { .. }
-                    | Event::KeyDown {
-                        keycode: Some(Keycode::Escape),
-                        ..
-                    } => should_quit = true,
-                    Event::KeyDown {
-                        keycode: Some(keycode),
-                        ..
-                    } => {
-                        let key = get_key_index(&keycode);
-                        if key <= 0xF {
-                            return key as u8;
-                        }
-                    }
-                    _ => {}
-                }
-            }
-            self.update_should_quit(should_quit);
-            if should_quit {
-                return 0;
-            }
+    fn wants_debug_info(&self) -> bool {
+        self.overlay
+    }
+
+    fn draw_debug_info(&mut self, info: &DebugInfo) {
+        if self.panel.get_or_insert_with(Panel::new).update(info) {
+            self.dirty = true;
         }
     }
+
+    fn poll_request(&mut self) -> Option<Request> {
+        self.requests.pop_front()
+    }
 }